@@ -0,0 +1,100 @@
+//! The project-level ldproxy configuration file.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+/// The environment variable containing the path of the [`LdproxyConfig`] file.
+pub const LDPROXY_ARGS_FILE_VAR: &str = "LDPROXY_ARGS_FILE";
+
+/// Base settings for ldproxy read from the TOML or JSON file in [`LDPROXY_ARGS_FILE_VAR`].
+///
+/// All settings given on the command-line take priority over the settings of this file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LdproxyConfig {
+    /// The linker to use if `--ldproxy-linker` is not given.
+    pub linker: Option<String>,
+    /// The working directory of the linker if `--ldproxy-cwd` is not given.
+    pub working_directory: Option<String>,
+    /// Whether to remove duplicate libs if `--ldproxy-dedup-libs` is not given.
+    pub dedup_libs: Option<bool>,
+    /// Arguments added after all command-line arguments.
+    pub extra_args: Vec<String>,
+    /// Arguments added before all command-line arguments.
+    pub prepend_args: Vec<String>,
+}
+
+impl LdproxyConfig {
+    /// Load the configuration file given by [`LDPROXY_ARGS_FILE_VAR`], if set.
+    pub fn try_from_env() -> Result<Option<Self>> {
+        match env::var_os(LDPROXY_ARGS_FILE_VAR) {
+            Some(file) if !file.is_empty() => Self::load(Path::new(&file)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Load the configuration from `file`.
+    ///
+    /// The file is parsed as JSON if it has a `.json` extension, and as TOML otherwise.
+    pub fn load(file: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(file)
+            .with_context(|| anyhow!("Failed to read ldproxy config '{}'", file.display()))?;
+        let is_json = file
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+
+        Self::parse(&contents, is_json)
+            .with_context(|| anyhow!("Failed to parse ldproxy config '{}'", file.display()))
+    }
+
+    fn parse(contents: &str, is_json: bool) -> Result<Self> {
+        Ok(if is_json {
+            serde_json::from_str(contents)?
+        } else {
+            toml::from_str(contents)?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_toml_and_json() {
+        let expected = LdproxyConfig {
+            linker: Some("xtensa-esp32-elf-gcc".into()),
+            working_directory: None,
+            dedup_libs: Some(true),
+            extra_args: vec!["-Wl,--gc-sections".into()],
+            prepend_args: vec!["-nostartfiles".into()],
+        };
+
+        let toml = r#"
+            linker = "xtensa-esp32-elf-gcc"
+            dedup_libs = true
+            extra_args = ["-Wl,--gc-sections"]
+            prepend_args = ["-nostartfiles"]
+        "#;
+        assert_eq!(LdproxyConfig::parse(toml, false).unwrap(), expected);
+
+        let json = r#"{
+            "linker": "xtensa-esp32-elf-gcc",
+            "dedup_libs": true,
+            "extra_args": ["-Wl,--gc-sections"],
+            "prepend_args": ["-nostartfiles"]
+        }"#;
+        assert_eq!(LdproxyConfig::parse(json, true).unwrap(), expected);
+
+        assert_eq!(
+            LdproxyConfig::parse("", false).unwrap(),
+            LdproxyConfig::default()
+        );
+        assert!(LdproxyConfig::parse("dedup = true", false).is_err());
+    }
+}
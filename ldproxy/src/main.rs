@@ -1,106 +1,217 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::ffi::OsString;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{self, Command};
 use std::vec::Vec;
-use std::fs;
 
-use anyhow::{bail, Result};
-use embuild::build;
-use embuild::cli::{ParseFrom, UnixCommandArgs};
+use anyhow::{anyhow, bail, Context, Result};
+use embuild::cli::ParseFrom;
+use embuild::{build, cli};
 use log::*;
+use serde::Serialize;
+
+use crate::config::{LdproxyConfig, LDPROXY_ARGS_FILE_VAR};
+use crate::response_file::{FileSystem, RealFileSystem};
+use crate::style::Style;
+
+mod config;
+mod response_file;
+mod style;
 
 /// Read esp-idf-sys output file and extract all cargo:rustc-link-arg directives.
 /// Returns (link_args, working_directory).
+///
+/// If the build directory contains more than one `esp-idf-sys-*` output file, the most
+/// recently written one is used.
 fn read_esp_idf_sys_link_args(target_dir: &Path) -> Result<(Vec<String>, Option<PathBuf>)> {
-    let mut link_args = Vec::new();
-    let mut working_dir: Option<PathBuf> = None;
-    
     let build_dir = target_dir.join("build");
     if !build_dir.exists() {
         debug!("Build directory does not exist: {:?}", build_dir);
-        return Ok((link_args, working_dir));
+        return Ok((Vec::new(), None));
     }
-    
+
+    let mut output_files = Vec::new();
     if let Ok(entries) = fs::read_dir(&build_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap().to_str().unwrap();
-                if dir_name.starts_with("esp-idf-sys-") {
-                    let output_file = path.join("output");
-                    if output_file.exists() {
-                        debug!("Reading esp-idf-sys output file: {:?}", output_file);
-                        if let Ok(content) = fs::read_to_string(&output_file) {
-                            let mut skip_next = false;
-                            for line in content.lines() {
-                                if let Some(arg) = line.strip_prefix("cargo:rustc-link-arg=") {
-                                    // Extract working directory
-                                    if arg == "--ldproxy-cwd" {
-                                        skip_next = true;
-                                        continue;
-                                    }
-                                    if skip_next {
-                                        working_dir = Some(PathBuf::from(arg));
-                                        info!("Extracted working directory: {:?}", working_dir);
-                                        skip_next = false;
-                                        continue;
-                                    }
-                                    // Skip --ldproxy-linker parameter
-                                    if arg == "--ldproxy-linker" {
-                                        skip_next = true;
-                                        continue;
-                                    }
-                                    // Skip other ldproxy-specific parameters
-                                    if !arg.starts_with("--ldproxy") {
-                                        link_args.push(arg.to_string());
-                                    }
-                                }
-                            }
-                            info!("Extracted {} link args from esp-idf-sys output", link_args.len());
-                        }
-                    }
-                    break;
-                }
+            let is_esp_idf_sys = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("esp-idf-sys-"))
+                .unwrap_or(false);
+            if !path.is_dir() || !is_esp_idf_sys {
+                continue;
+            }
+
+            let output_file = path.join("output");
+            if let Ok(modified) = fs::metadata(&output_file).and_then(|m| m.modified()) {
+                output_files.push((modified, output_file));
             }
         }
     }
-    
+
+    // Order by modification time and then by path, so that the choice does not depend on
+    // the order in which the directory entries are returned.
+    let output_file = match output_files.into_iter().max() {
+        Some((_, output_file)) => output_file,
+        None => return Ok((Vec::new(), None)),
+    };
+
+    debug!("Reading esp-idf-sys output file: {:?}", output_file);
+    let content = fs::read_to_string(&output_file)?;
+    let (link_args, working_dir) = parse_esp_idf_sys_link_args(&content);
+
+    if let Some(working_dir) = &working_dir {
+        info!("Extracted working directory: {:?}", working_dir);
+    }
+    info!(
+        "Extracted {} link args from esp-idf-sys output",
+        link_args.len()
+    );
+
     Ok((link_args, working_dir))
 }
 
-fn main() -> Result<()> {
+/// Extract all cargo:rustc-link-arg directives from the `content` of an esp-idf-sys output
+/// file, in the order they appear.
+///
+/// The `--ldproxy-*` control arguments and the values of `--ldproxy-linker` and
+/// `--ldproxy-cwd` are removed; the value of the last `--ldproxy-cwd` is returned as the
+/// working directory.
+fn parse_esp_idf_sys_link_args(content: &str) -> (Vec<String>, Option<PathBuf>) {
+    let mut link_args = Vec::new();
+    let mut working_dir = None;
+    // The control argument whose value is the next link arg.
+    let mut pending_value_of = None;
+
+    for arg in content
+        .lines()
+        .filter_map(|line| line.strip_prefix("cargo:rustc-link-arg="))
+    {
+        if let Some(control) = pending_value_of.take() {
+            if control == build::LDPROXY_WORKING_DIRECTORY_ARG.name {
+                working_dir = Some(PathBuf::from(arg));
+            }
+            continue;
+        }
+
+        if let Some(control) = arg.strip_prefix("--") {
+            let (name, value) = match control.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (control, None),
+            };
+
+            if name == build::LDPROXY_WORKING_DIRECTORY_ARG.name {
+                match value {
+                    Some(value) => working_dir = Some(PathBuf::from(value)),
+                    None => pending_value_of = Some(name),
+                }
+                continue;
+            }
+            if name == build::LDPROXY_LINKER_ARG.name {
+                if value.is_none() {
+                    pending_value_of = Some(name);
+                }
+                continue;
+            }
+            // Skip other ldproxy-specific parameters
+            if name.starts_with("ldproxy") {
+                continue;
+            }
+        }
+
+        link_args.push(arg.to_owned());
+    }
+
+    (link_args, working_dir)
+}
+
+fn main() {
+    let args = env::args_os().skip(1).collect::<Vec<_>>();
+
+    // The style is needed before the logger is initialized, so `--ldproxy-color` is taken
+    // from the command-line as is, without expanding response files.
+    let color = build::LDPROXY_COLOR_ARG
+        .parse_from(
+            &mut args
+                .iter()
+                .filter_map(|arg| arg.to_str().map(str::to_owned))
+                .collect(),
+        )
+        .ok()
+        .and_then(|v| v.into_iter().next_back());
+    let style = match Style::from_env(color.as_deref()) {
+        Ok(style) => style,
+        Err(e) => {
+            eprintln!("ldproxy error: {e:?}");
+            process::exit(1);
+        }
+    };
+
     env_logger::Builder::from_env(
-        env_logger::Env::new()
-            .write_style_or("LDPROXY_LOG_STYLE", "Auto")
-            .filter_or("LDPROXY_LOG", LevelFilter::Info.to_string()),
+        env_logger::Env::new().filter_or("LDPROXY_LOG", LevelFilter::Info.to_string()),
     )
+    .write_style(style.write_style())
     .target(env_logger::Target::Stderr)
-    .format_level(false)
-    .format_indent(None)
-    .format_module_path(false)
-    .format_timestamp(None)
+    .format(Style::format_log)
     .init();
 
+    if let Err(e) = run(style, args, &RealFileSystem) {
+        style.print_error(format_args!("{e:?}"));
+        process::exit(1);
+    }
+}
+
+/// Run ldproxy with the command-line arguments `args`, reading response files from
+/// `file_system`.
+fn run(style: Style, args: Vec<OsString>, file_system: &impl FileSystem) -> Result<()> {
     info!("Running ldproxy");
 
-    debug!("Raw link arguments: {:?}", env::args());
+    debug!("Raw link arguments: {args:?}");
 
-    let mut args = args()?;
+    let mut args = response_file::expand_args(args, file_system)?;
 
     debug!("Link arguments: {args:?}");
 
-    let [linker, remove_duplicate_libs, cwd] = [
+    let [linker, remove_duplicate_libs, cwd, emit_command_file, dry_run, _color] = [
         &build::LDPROXY_LINKER_ARG,
         &build::LDPROXY_DEDUP_LIBS_ARG,
         &build::LDPROXY_WORKING_DIRECTORY_ARG,
+        &build::LDPROXY_EMIT_COMMAND_FILE_ARG,
+        &build::LDPROXY_DRY_RUN_ARG,
+        &build::LDPROXY_COLOR_ARG,
     ]
     .parse_from(&mut args);
 
+    // The wrapped tool may also be invoked as a compiler driver, in which case the
+    // arguments must not be touched, as all processing below only applies to linking.
+    let passthrough = is_compile_invocation(&args);
+    if passthrough {
+        info!("Compile-only invocation detected, passing all arguments through unchanged");
+    }
+
+    let config = LdproxyConfig::try_from_env()?.unwrap_or_default();
+    if !passthrough && (!config.prepend_args.is_empty() || !config.extra_args.is_empty()) {
+        info!(
+            "Adding {} leading and {} trailing args from {LDPROXY_ARGS_FILE_VAR}",
+            config.prepend_args.len(),
+            config.extra_args.len()
+        );
+        args.splice(0..0, config.prepend_args.iter().cloned());
+        args.extend(config.extra_args.iter().cloned());
+    }
+
     // Try to get linker from arguments first
     let linker = linker
         .ok()
         .and_then(|v| v.into_iter().next_back())
+        .or_else(|| {
+            let linker = config.linker.clone()?;
+            info!("Using linker from {LDPROXY_ARGS_FILE_VAR}: {linker}");
+            Some(linker)
+        })
         // If not in arguments, try environment variables (needed for RISC-V targets)
         .or_else(|| {
             // Check CC environment variables for all RISC-V ESP-IDF targets
@@ -133,36 +244,50 @@ fn main() -> Result<()> {
 
     debug!("Actual linker executable: {linker}");
 
-    let mut cwd = cwd.ok().and_then(|v| v.into_iter().next_back());
-    let remove_duplicate_libs = remove_duplicate_libs.is_ok();
-
-    // Infer target directory from rustc arguments
-    // Arguments contain paths like: /path/to/target/riscv32imafc-esp-espidf/debug/deps/xxx.rlib
-    let mut target_dir: Option<PathBuf> = None;
-    debug!("Searching for target directory in {} arguments", args.len());
-    for arg in &args {
-        if arg.contains("/target/") && arg.contains("/deps/") {
-            debug!("Found potential target path: {}", arg);
-            if let Some(pos) = arg.rfind("/deps/") {
-                let deps_path = &arg[..pos];
-                target_dir = Some(PathBuf::from(deps_path));
-                debug!("Inferred target directory: {:?}", target_dir);
-                break;
+    let mut cwd = cwd
+        .ok()
+        .and_then(|v| v.into_iter().next_back())
+        .or_else(|| {
+            let cwd = config.working_directory.clone()?;
+            info!("Using working directory from {LDPROXY_ARGS_FILE_VAR}: {cwd}");
+            Some(cwd)
+        });
+    let remove_duplicate_libs = remove_duplicate_libs.is_ok()
+        || match config.dedup_libs {
+            Some(dedup_libs) => {
+                info!("Using dedup_libs = {dedup_libs} from {LDPROXY_ARGS_FILE_VAR}");
+                dedup_libs
             }
-        }
-    }
+            None => false,
+        };
+    let emit_command_file = emit_command_file
+        .ok()
+        .and_then(|v| v.into_iter().next_back());
+    let dry_run = dry_run.is_ok();
+
+    let target_dir = if passthrough {
+        None
+    } else {
+        infer_target_dir(&args)
+    };
 
     // Read all link arguments and working directory from esp-idf-sys output file
     if let Some(ref target_dir) = target_dir {
-        info!("Reading esp-idf-sys link args from target directory: {:?}", target_dir);
+        info!(
+            "Reading esp-idf-sys link args from target directory: {:?}",
+            target_dir
+        );
         match read_esp_idf_sys_link_args(target_dir) {
             Ok((esp_link_args, esp_cwd)) => {
                 // Use working directory from esp-idf-sys if available
                 if let Some(esp_working_dir) = esp_cwd {
-                    info!("Using working directory from esp-idf-sys: {:?}", esp_working_dir);
+                    info!(
+                        "Using working directory from esp-idf-sys: {:?}",
+                        esp_working_dir
+                    );
                     cwd = Some(esp_working_dir.to_str().unwrap().to_string());
                 }
-                
+
                 if !esp_link_args.is_empty() {
                     info!("Applying {} ESP-IDF link args", esp_link_args.len());
                     args.extend(esp_link_args);
@@ -176,7 +301,7 @@ fn main() -> Result<()> {
         }
     }
 
-    let args = if remove_duplicate_libs {
+    let args = if remove_duplicate_libs && !passthrough {
         debug!("Duplicate libs removal requested");
 
         let mut libs = HashMap::<String, usize>::new();
@@ -210,61 +335,77 @@ fn main() -> Result<()> {
         args
     };
 
+    let command_line = cli::format_shell_command(&linker, &args);
+    debug!("Full linker command: {command_line}");
+
+    if let Some(command_file) = &emit_command_file {
+        let command = LinkerCommand::resolve(&linker, &args, cwd.as_deref(), &env::current_dir()?)?;
+        command.write_to(Path::new(command_file))?;
+        info!("Wrote linker command to {command_file}");
+    }
+
+    if dry_run {
+        info!("Dry run requested, not calling the linker: {command_line}");
+        return Ok(());
+    }
+
     let mut cmd = Command::new(&linker);
     if let Some(ref cwd) = cwd {
         cmd.current_dir(cwd);
         info!("Linker working directory: {}", cwd);
     }
-    
+
     info!("Linker command: {} (with {} args)", linker, args.len());
-    
+
     // Use response file for commands with >500 arguments to avoid command line length limits
     let use_response_file = args.len() > 500;
-    
+
     if use_response_file {
         info!("Using response file due to {} args", args.len());
-        
+
         let response_file = env::temp_dir().join(format!("ldproxy-{}.rsp", std::process::id()));
         let response_content = args.join("\n");
-        
-        if let Err(e) = fs::write(&response_file, response_content) {
-            warn!("Failed to write response file: {}, falling back to direct args", e);
+
+        if let Err(e) = embuild::fs::atomic_write(&response_file, response_content) {
+            warn!(
+                "Failed to write response file: {:#}, falling back to direct args",
+                e
+            );
             cmd.args(&args);
         } else {
-            info!("Wrote {} args to response file: {:?}", args.len(), response_file);
+            info!(
+                "Wrote {} args to response file: {:?}",
+                args.len(),
+                response_file
+            );
             // Use GCC's @file syntax
             cmd.arg(format!("@{}", response_file.display()));
         }
     } else {
         cmd.args(&args);
     }
-    
-    if args.len() < 50 {
-        debug!("Full linker command: {} {}", linker, args.join(" "));
-    } else {
-        debug!("First 10 args: {}", args.iter().take(10).cloned().collect::<Vec<_>>().join(" "));
-        debug!("Last 10 args: {}", args.iter().skip(args.len().saturating_sub(10)).cloned().collect::<Vec<_>>().join(" "));
-    }
 
     debug!("Calling actual linker: {cmd:?}");
 
     let output = cmd.output()?;
-    
+
     // Clean up response file if used
     if use_response_file {
         let response_file = env::temp_dir().join(format!("ldproxy-{}.rsp", std::process::id()));
         let _ = fs::remove_file(&response_file);
     }
-    
-    let stdout = String::from_utf8(output.stdout)?;
-    let stderr = String::from_utf8(output.stderr)?;
+
+    // The output is only displayed, so don't fail on linkers printing non-UTF-8 bytes
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
 
     debug!("==============Linker stdout:\n{stdout}\n==============");
     debug!("==============Linker stderr:\n{stderr}\n==============");
 
     if !output.status.success() {
+        style.print_linker_stderr(&stderr);
         bail!(
-            "Linker {linker} failed: {}\nSTDERR OUTPUT:\n{stderr}",
+            "Linker {linker} failed: {}\nCOMMAND:\n{command_line}",
             output.status
         );
     }
@@ -276,37 +417,394 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-/// Get all arguments
-///
-/// **Currently only supports gcc-like arguments**
+/// Whether `args` are the arguments of a compile-only invocation of a compiler driver,
+/// i.e. they preprocess, compile or assemble (`-E`, `-S`, `-c`) or contain C/C++ sources.
+fn is_compile_invocation(args: &[String]) -> bool {
+    const SOURCE_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "c++"];
+
+    args.iter().any(|arg| {
+        matches!(arg.as_str(), "-c" | "-E" | "-S")
+            || !arg.starts_with('-')
+                && Path::new(arg)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| SOURCE_EXTENSIONS.contains(&ext))
+                    .unwrap_or(false)
+    })
+}
+
+/// Infer the target directory from rustc arguments.
 ///
-/// FIXME: handle other linker flavors (https://doc.rust-lang.org/rustc/codegen-options/index.html#linker-flavor)
-fn args() -> Result<Vec<String>> {
-    let mut result = Vec::new();
-
-    for arg in env::args().skip(1) {
-        // Rustc could invoke use with response file arguments, so we could get arguments
-        // like: `@<link-args-file>` (as per `@file` section of
-        // https://gcc.gnu.org/onlinedocs/gcc-11.2.0/gcc/Overall-Options.html)
-        //
-        // Deal with that
-        if let Some(rsp_file_str) = arg.strip_prefix('@') {
-            let rsp_file = Path::new(rsp_file_str);
-            // get all arguments from the response file if it exists
-            if rsp_file.exists() {
-                let contents = std::fs::read_to_string(rsp_file)?;
-                debug!("Contents of {}: {}", rsp_file_str, contents);
-
-                result.extend(UnixCommandArgs::new(&contents));
-            }
-            // otherwise just add the argument as normal
-            else {
-                result.push(arg);
+/// Arguments contain paths like: /path/to/target/riscv32imafc-esp-espidf/debug/deps/xxx.rlib
+fn infer_target_dir(args: &[String]) -> Option<PathBuf> {
+    debug!("Searching for target directory in {} arguments", args.len());
+    for arg in args {
+        if arg.contains("/target/") && arg.contains("/deps/") {
+            debug!("Found potential target path: {}", arg);
+            if let Some(pos) = arg.rfind("/deps/") {
+                let target_dir = PathBuf::from(&arg[..pos]);
+                debug!("Inferred target directory: {:?}", target_dir);
+                return Some(target_dir);
             }
+        }
+    }
+
+    None
+}
+
+/// The resolved linker invocation written by `--ldproxy-emit-command-file`.
+#[derive(Debug, Serialize)]
+struct LinkerCommand {
+    /// The absolute path of the linker executable.
+    linker: PathBuf,
+    /// All arguments passed to the linker, with the paths in them made absolute.
+    args: Vec<String>,
+    /// The absolute working directory of the linker.
+    cwd: PathBuf,
+    /// The environment variables set for the linker in addition to the inherited
+    /// environment.
+    ///
+    /// Always empty, as ldproxy runs the linker with its own environment unchanged.
+    env: BTreeMap<String, String>,
+}
+
+impl LinkerCommand {
+    /// Resolve the invocation of `linker` with `args` in the working directory `cwd`,
+    /// where all relative paths are relative to `current_dir`.
+    fn resolve(
+        linker: &str,
+        args: &[String],
+        cwd: Option<&str>,
+        current_dir: &Path,
+    ) -> Result<Self> {
+        let cwd = match cwd {
+            Some(cwd) => current_dir.join(cwd),
+            None => current_dir.to_owned(),
+        };
+
+        // A linker without a path separator is looked up in `PATH`, otherwise it is
+        // relative to the working directory of the linker.
+        let linker_path = Path::new(linker);
+        let linker = if linker_path.components().count() > 1 {
+            cwd.join(linker_path)
+        } else {
+            which::which(linker)
+                .with_context(|| anyhow!("Cannot find linker '{linker}' in PATH"))?
+        };
+
+        Ok(Self {
+            linker,
+            args: absolute_path_args(args, &cwd),
+            cwd,
+            env: BTreeMap::new(),
+        })
+    }
+
+    /// Write this command as JSON to `path`.
+    fn write_to(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        embuild::fs::atomic_write(path, json)
+            .with_context(|| anyhow!("Failed to write linker command file '{}'", path.display()))
+    }
+}
+
+/// Make the paths in the linker `args` absolute against `cwd`.
+///
+/// Only arguments known to be paths are changed:
+/// - the values of `-o`, `-L` and `-T` (separate or attached) and of their long forms,
+/// - `-Wl,-Map=<file>` and `@file` response files,
+/// - bare input files, which are arguments that contain a path separator or have the
+///   extension of an object file, library or linker script.
+///
+/// The values of options like `-z`, `-m` or `-Xlinker` are kept as is.
+fn absolute_path_args(args: &[String], cwd: &Path) -> Vec<String> {
+    const PATH_OPTS: [&str; 6] = ["-o", "-L", "-T", "--output", "--library-path", "--script"];
+    const PATH_PREFIXES: [&str; 8] = [
+        "-o",
+        "-L",
+        "-T",
+        "--output=",
+        "--library-path=",
+        "--script=",
+        "-Wl,-Map=",
+        "-Wl,--Map=",
+    ];
+    /// Options whose separate value is not a path.
+    const VALUE_OPTS: [&str; 21] = [
+        "-Xlinker",
+        "-Xassembler",
+        "-Xpreprocessor",
+        "-l",
+        "-u",
+        "-e",
+        "-x",
+        "-z",
+        "-m",
+        "-h",
+        "-A",
+        "--defsym",
+        "--entry",
+        "--undefined",
+        "-rpath",
+        "-rpath-link",
+        "-soname",
+        "--section-start",
+        "-Ttext",
+        "-Tdata",
+        "-Tbss",
+    ];
+    const FILE_EXTENSIONS: [&str; 9] = ["o", "obj", "a", "lib", "rlib", "so", "ld", "x", "rsp"];
+
+    let absolute = |path: &str| cwd.join(path).display().to_string();
+    let is_input_file = |arg: &str| {
+        arg.contains(['/', '\\'])
+            || Path::new(arg)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| FILE_EXTENSIONS.contains(&ext))
+                .unwrap_or(false)
+    };
+
+    let mut result = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if PATH_OPTS.contains(&arg.as_str()) {
+            result.push(arg.clone());
+            result.extend(args.next().map(|value| absolute(value)));
+        } else if VALUE_OPTS.contains(&arg.as_str()) {
+            result.push(arg.clone());
+            result.extend(args.next().cloned());
+        } else if arg.starts_with("-T")
+            && [
+                "text",
+                "data",
+                "bss",
+                "text-segment",
+                "rodata-segment",
+                "ldata-segment",
+            ]
+            .iter()
+            .any(|section| arg[2..].starts_with(section))
+        {
+            // `-Ttext=<address>` and similar are not linker scripts.
+            result.push(arg.clone());
+        } else if let Some(path) = arg.strip_prefix('@') {
+            result.push(format!("@{}", absolute(path)));
+        } else if let Some((prefix, path)) = PATH_PREFIXES
+            .iter()
+            .find_map(|prefix| Some((prefix, arg.strip_prefix(prefix)?)))
+        {
+            result.push(format!("{prefix}{}", absolute(path)));
+        } else if !arg.starts_with('-') && is_input_file(arg) {
+            result.push(absolute(arg));
         } else {
-            result.push(arg);
+            result.push(arg.clone());
         }
     }
 
-    Ok(result)
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn esp_idf_sys_link_args_keep_source_order() {
+        let content = "\
+cargo:rustc-cfg=esp_idf_version_major=\"5\"
+cargo:rustc-link-arg=--ldproxy-linker
+cargo:rustc-link-arg=/home/user/.espressif/tools/riscv32-esp-elf/bin/riscv32-esp-elf-gcc
+cargo:rustc-link-arg=--ldproxy-cwd
+cargo:rustc-link-arg=/project/target/riscv32imc-esp-espidf/debug/build/esp-idf-sys-1234/out
+cargo:rustc-link-arg=-nostartfiles
+cargo:rustc-link-arg=-march=rv32imc_zicsr_zifencei
+cargo:rustc-link-search=native=/project/out/build/esp-idf/freertos
+cargo:rustc-link-arg=-Wl,--start-group
+cargo:rustc-link-arg=esp-idf/freertos/libfreertos.a
+cargo:rustc-link-arg=esp-idf/newlib/libnewlib.a
+cargo:rustc-link-arg=-lc
+cargo:rustc-link-arg=--ldproxy-dedup-libs
+cargo:rustc-link-arg=-Wl,--end-group
+cargo:rustc-link-arg=-Wl,--cref
+cargo:rustc-link-arg=-Wl,--Map=/project/out/build/esp-idf-sys.map
+cargo:rerun-if-changed=/project/sdkconfig.defaults
+";
+
+        let (link_args, working_dir) = parse_esp_idf_sys_link_args(content);
+
+        assert_eq!(
+            link_args,
+            [
+                "-nostartfiles",
+                "-march=rv32imc_zicsr_zifencei",
+                "-Wl,--start-group",
+                "esp-idf/freertos/libfreertos.a",
+                "esp-idf/newlib/libnewlib.a",
+                "-lc",
+                "-Wl,--end-group",
+                "-Wl,--cref",
+                "-Wl,--Map=/project/out/build/esp-idf-sys.map",
+            ]
+        );
+        assert_eq!(
+            working_dir,
+            Some(PathBuf::from(
+                "/project/target/riscv32imc-esp-espidf/debug/build/esp-idf-sys-1234/out"
+            ))
+        );
+    }
+
+    #[test]
+    fn esp_idf_sys_link_args_linker_value_is_not_working_dir() {
+        let content = "\
+cargo:rustc-link-arg=--ldproxy-cwd=/work
+cargo:rustc-link-arg=-mlongcalls
+cargo:rustc-link-arg=--ldproxy-linker
+cargo:rustc-link-arg=xtensa-esp32-elf-gcc
+cargo:rustc-link-arg=-Wl,--gc-sections
+";
+
+        let (link_args, working_dir) = parse_esp_idf_sys_link_args(content);
+
+        assert_eq!(link_args, ["-mlongcalls", "-Wl,--gc-sections"]);
+        assert_eq!(working_dir, Some(PathBuf::from("/work")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn linker_command_paths_are_absolute() {
+        let args = ["-o", "app.elf"].map(str::to_owned);
+        let command = LinkerCommand::resolve(
+            "tools/bin/riscv32-esp-elf-gcc",
+            &args,
+            Some("build"),
+            Path::new("/project"),
+        )
+        .unwrap();
+
+        assert_eq!(command.cwd, Path::new("/project/build"));
+        assert_eq!(
+            command.linker,
+            Path::new("/project/build/tools/bin/riscv32-esp-elf-gcc")
+        );
+
+        let json: serde_json::Value = serde_json::to_value(&command).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "linker": "/project/build/tools/bin/riscv32-esp-elf-gcc",
+                "args": ["-o", "/project/build/app.elf"],
+                "cwd": "/project/build",
+                "env": {},
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn linker_command_path_args_are_absolute() {
+        let args = [
+            "main.o",
+            "/abs/lib.a",
+            "-ofoo.elf",
+            "-L",
+            "libs",
+            "-Lmore/libs",
+            "-T",
+            "memory.x",
+            "-Tlink.x",
+            "-Wl,-Map=app.map",
+            "-Wl,--gc-sections",
+            "@args.rsp",
+            "-Xlinker",
+            "--defsym=a=1",
+            "-l",
+            "c",
+            "-mlongcalls",
+            "-z",
+            "noexecstack",
+            "-m",
+            "elf32xtensa",
+            "--defsym",
+            "_stack=0x1000",
+            "-rpath",
+            "lib/runtime",
+            "-Ttext=0x40080000",
+            "-Tdata",
+            "0x3ffb0000",
+            "--script",
+            "esp32.ld",
+            "--output=app",
+            "libfoo.a",
+            "-Wl,--cref",
+        ]
+        .map(str::to_owned);
+        let command = LinkerCommand::resolve("/bin/gcc", &args, None, Path::new("/work")).unwrap();
+
+        assert_eq!(command.cwd, Path::new("/work"));
+        assert_eq!(
+            command.args,
+            [
+                "/work/main.o",
+                "/abs/lib.a",
+                "-o/work/foo.elf",
+                "-L",
+                "/work/libs",
+                "-L/work/more/libs",
+                "-T",
+                "/work/memory.x",
+                "-T/work/link.x",
+                "-Wl,-Map=/work/app.map",
+                "-Wl,--gc-sections",
+                "@/work/args.rsp",
+                "-Xlinker",
+                "--defsym=a=1",
+                "-l",
+                "c",
+                "-mlongcalls",
+                "-z",
+                "noexecstack",
+                "-m",
+                "elf32xtensa",
+                "--defsym",
+                "_stack=0x1000",
+                "-rpath",
+                "lib/runtime",
+                "-Ttext=0x40080000",
+                "-Tdata",
+                "0x3ffb0000",
+                "--script",
+                "/work/esp32.ld",
+                "--output=/work/app",
+                "/work/libfoo.a",
+                "-Wl,--cref",
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_compile_invocations() {
+        let args = |args: &[&str]| args.iter().map(|&a| a.to_owned()).collect::<Vec<_>>();
+
+        assert!(is_compile_invocation(&args(&[
+            "-c", "-o", "main.o", "main.c"
+        ])));
+        assert!(is_compile_invocation(&args(&["-E", "-dM", "-"])));
+        assert!(is_compile_invocation(&args(&["-S", "-o", "main.s"])));
+        assert!(is_compile_invocation(&args(&[
+            "-O2",
+            "src/lib.cpp",
+            "-o",
+            "lib"
+        ])));
+        assert!(!is_compile_invocation(&args(&[
+            "-nostartfiles",
+            "/project/target/riscv32imc-esp-espidf/debug/deps/app-0123.app.o",
+            "-Wl,--gc-sections",
+            "-lc",
+            "-o",
+            "app",
+        ])));
+    }
 }
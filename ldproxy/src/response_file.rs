@@ -0,0 +1,207 @@
+//! Expanding `@file` response file arguments.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use embuild::cli::UnixCommandArgs;
+use log::*;
+
+/// The maximum nesting depth of response files, as response files including each other
+/// through different paths (ex. `@../dir/a.rsp`) are not detected as recursive.
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// The filesystem response files are read from.
+pub trait FileSystem {
+    /// Read the response file `path`, or return [`None`] if it does not exist.
+    fn read_response_file(&self, path: &Path) -> Result<Option<String>>;
+}
+
+/// The [`FileSystem`] of the host.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_response_file(&self, path: &Path) -> Result<Option<String>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        fs::read_to_string(path)
+            .map(Some)
+            .with_context(|| anyhow!("Failed to read response file '{}'", path.display()))
+    }
+}
+
+/// Expand all `@file` response file arguments in `args`, reading the response files
+/// from `file_system`.
+///
+/// Response files may contain `@file` arguments themselves, which are expanded as well.
+/// A `@file` argument whose file does not exist is kept as is.
+///
+/// **Currently only supports gcc-like arguments**
+///
+/// FIXME: handle other linker flavors (https://doc.rust-lang.org/rustc/codegen-options/index.html#linker-flavor)
+pub fn expand_args(args: Vec<OsString>, file_system: &impl FileSystem) -> Result<Vec<String>> {
+    let args = args
+        .into_iter()
+        .map(|arg| {
+            arg.into_string()
+                .map_err(|arg| anyhow!("Argument {arg:?} is not valid UTF-8"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut result = Vec::new();
+    expand_into(args, file_system, &mut Vec::new(), &mut result)?;
+
+    Ok(result)
+}
+
+/// Expand `args` into `result`, `parents` are the response files currently being
+/// expanded.
+fn expand_into(
+    args: Vec<String>,
+    file_system: &impl FileSystem,
+    parents: &mut Vec<PathBuf>,
+    result: &mut Vec<String>,
+) -> Result<()> {
+    for arg in args {
+        // Rustc could invoke use with response file arguments, so we could get arguments
+        // like: `@<link-args-file>` (as per `@file` section of
+        // https://gcc.gnu.org/onlinedocs/gcc-11.2.0/gcc/Overall-Options.html)
+        //
+        // Deal with that
+        let rsp_file = match arg.strip_prefix('@') {
+            Some(rsp_file) => normalize(Path::new(rsp_file)),
+            None => {
+                result.push(arg);
+                continue;
+            }
+        };
+
+        // get all arguments from the response file if it exists
+        // otherwise just add the argument as normal
+        let contents = match file_system.read_response_file(&rsp_file)? {
+            Some(contents) => contents,
+            None => {
+                result.push(arg);
+                continue;
+            }
+        };
+        debug!("Contents of {}: {}", rsp_file.display(), contents);
+
+        if parents.contains(&rsp_file) {
+            bail!(
+                "Response file '{}' recursively includes itself",
+                rsp_file.display()
+            );
+        }
+        if parents.len() >= MAX_NESTING_DEPTH {
+            bail!(
+                "Response file '{}' is nested more than {MAX_NESTING_DEPTH} levels deep",
+                rsp_file.display()
+            );
+        }
+
+        parents.push(rsp_file);
+        expand_into(response_file_args(&contents), file_system, parents, result)?;
+        parents.pop();
+    }
+
+    Ok(())
+}
+
+/// Remove the `.` components of `path`, so that the same response file is always
+/// referred to by the same path.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+/// Split the `contents` of a response file into separate arguments.
+///
+/// Response files written on Windows may use CRLF line endings. As the unix argument
+/// parser only treats `\n` as whitespace, every CRLF is treated as a plain LF so that no
+/// argument ends up with a trailing `\r`. A lone `\r` is kept as is.
+fn response_file_args(contents: &str) -> Vec<String> {
+    UnixCommandArgs::new(&contents.replace("\r\n", "\n")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// A [`FileSystem`] containing only the response files of the map.
+    impl FileSystem for HashMap<PathBuf, String> {
+        fn read_response_file(&self, path: &Path) -> Result<Option<String>> {
+            Ok(self.get(path).cloned())
+        }
+    }
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), contents.to_string()))
+            .collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn expand_crlf_response_file() {
+        let fs = files(&[(
+            "link.rsp",
+            "-L/path/to/libs\r\n-lfoo\r\n\"-Wl,--defsym=a\rb\"\r\n-o out.elf\r\n",
+        )]);
+
+        assert_eq!(
+            expand_args(args(&["-nostdlib", "@link.rsp", "-lbar"]), &fs).unwrap(),
+            [
+                "-nostdlib",
+                "-L/path/to/libs",
+                "-lfoo",
+                "-Wl,--defsym=a\rb",
+                "-o",
+                "out.elf",
+                "-lbar"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_nested_response_files() {
+        let fs = files(&[
+            ("outer.rsp", "-la @inner.rsp -lb @inner.rsp"),
+            ("inner.rsp", "-lc\r\n@missing.rsp\r\n"),
+        ]);
+
+        assert_eq!(
+            expand_args(args(&["@outer.rsp", "-o", "app"]), &fs).unwrap(),
+            [
+                "-la",
+                "-lc",
+                "@missing.rsp",
+                "-lb",
+                "-lc",
+                "@missing.rsp",
+                "-o",
+                "app"
+            ]
+        );
+
+        let fs = files(&[("a.rsp", "-la @b.rsp"), ("b.rsp", "@a.rsp")]);
+        assert!(expand_args(args(&["@a.rsp"]), &fs).is_err());
+
+        let fs = files(&[("a.rsp", "-la @./a.rsp")]);
+        assert!(expand_args(args(&["@a.rsp"]), &fs).is_err());
+
+        let fs = files(&[("dir/a.rsp", "-la @./dir/./a.rsp")]);
+        assert!(expand_args(args(&["@./dir/a.rsp"]), &fs).is_err());
+    }
+}
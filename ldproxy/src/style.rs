@@ -0,0 +1,179 @@
+//! Colored output of ldproxy's own diagnostics.
+
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+/// The environment variable controlling the style of ldproxy's output (`auto`,
+/// `always` or `never`).
+pub const LDPROXY_LOG_STYLE_VAR: &str = "LDPROXY_LOG_STYLE";
+/// The environment variable that disables colored output if set to a non-empty value
+/// (see <https://no-color.org>).
+const NO_COLOR_VAR: &str = "NO_COLOR";
+
+/// When to use colored output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Only if stderr is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "always" => Self::Always,
+            "never" => Self::Never,
+            _ => bail!("Invalid color mode '{s}', expected 'auto', 'always' or 'never'"),
+        })
+    }
+}
+
+/// The style of the diagnostics ldproxy writes to stderr.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    color: bool,
+}
+
+impl Style {
+    /// Determine the style from the `--ldproxy-color` value `color_arg`, or the
+    /// environment if not given.
+    ///
+    /// `--ldproxy-color` takes priority over `NO_COLOR`, which takes priority over
+    /// [`LDPROXY_LOG_STYLE_VAR`].
+    pub fn from_env(color_arg: Option<&str>) -> Result<Self> {
+        let color_arg = color_arg.map(str::parse).transpose()?;
+        let log_style = env::var(LDPROXY_LOG_STYLE_VAR)
+            .ok()
+            .filter(|style| !style.is_empty());
+        let no_color = env::var_os(NO_COLOR_VAR)
+            .map(|v| !v.is_empty())
+            .unwrap_or(false);
+
+        Ok(Self::resolve(
+            color_arg,
+            log_style.as_deref(),
+            no_color,
+            io::stderr().is_terminal(),
+        ))
+    }
+
+    fn resolve(
+        color_arg: Option<ColorMode>,
+        log_style: Option<&str>,
+        no_color: bool,
+        stderr_is_tty: bool,
+    ) -> Self {
+        let mode = match color_arg {
+            Some(mode) => mode,
+            None if no_color => ColorMode::Never,
+            // Invalid values are treated like `auto`, same as env_logger does.
+            None => log_style
+                .and_then(|style| style.parse().ok())
+                .unwrap_or(ColorMode::Auto),
+        };
+
+        Self {
+            color: match mode {
+                ColorMode::Auto => stderr_is_tty,
+                ColorMode::Always => true,
+                ColorMode::Never => false,
+            },
+        }
+    }
+
+    /// The write style of the logger.
+    pub fn write_style(self) -> env_logger::WriteStyle {
+        if self.color {
+            env_logger::WriteStyle::Always
+        } else {
+            env_logger::WriteStyle::Never
+        }
+    }
+
+    /// Format a log `record`, with warnings and errors colored if the logger writes
+    /// in color.
+    pub fn format_log(
+        buf: &mut env_logger::fmt::Formatter,
+        record: &log::Record,
+    ) -> io::Result<()> {
+        let color = match record.level() {
+            log::Level::Error => env_logger::fmt::Color::Red,
+            log::Level::Warn => env_logger::fmt::Color::Yellow,
+            _ => return writeln!(buf, "{}", record.args()),
+        };
+
+        let mut style = buf.style();
+        style.set_color(color).set_bold(true);
+        writeln!(buf, "{}", style.value(record.args()))
+    }
+
+    /// Print the stderr output of the wrapped linker, delimited by separators from
+    /// ldproxy's own messages.
+    pub fn print_linker_stderr(self, stderr: &str) {
+        let mut out = self.stderr();
+
+        self.separator(&mut out, "linker stderr");
+        let stderr = stderr.trim_end();
+        if !stderr.is_empty() {
+            let _ = writeln!(out, "{stderr}");
+        }
+        self.separator(&mut out, "end of linker stderr");
+    }
+
+    /// Print the error that made ldproxy fail.
+    pub fn print_error(self, error: impl Display) {
+        let mut out = self.stderr();
+
+        let _ = out.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true));
+        let _ = write!(out, "ldproxy error");
+        let _ = out.reset();
+        let _ = writeln!(out, ": {error}");
+    }
+
+    fn separator(self, out: &mut StandardStream, title: &str) {
+        let _ = out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true));
+        let _ = write!(out, "========== {title} ==========");
+        let _ = out.reset();
+        let _ = writeln!(out);
+    }
+
+    fn stderr(self) -> StandardStream {
+        StandardStream::stderr(if self.color {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_color() {
+        let color = |arg, style, no_color, tty| Style::resolve(arg, style, no_color, tty).color;
+
+        assert!(color(None, None, false, true));
+        assert!(!color(None, None, false, false));
+        assert!(!color(None, None, true, true));
+        assert!(!color(None, Some("never"), false, true));
+        assert!(color(None, Some("Always"), false, false));
+        assert!(color(None, Some("bogus"), false, true));
+        assert!(!color(None, Some("always"), true, true));
+        assert!(color(Some(ColorMode::Always), None, true, false));
+        assert!(!color(Some(ColorMode::Never), Some("always"), false, true));
+
+        assert_eq!("NEVER".parse::<ColorMode>().unwrap(), ColorMode::Never);
+        assert!("sometimes".parse::<ColorMode>().is_err());
+    }
+}
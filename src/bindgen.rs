@@ -111,6 +111,52 @@ impl Factory {
         self
     }
 
+    /// Add the clang args required for generating bindings for the ESP rust target
+    /// `triple` (ex. `xtensa-esp32-espidf` or `riscv32imc-esp-espidf`) using `toolchain`.
+    ///
+    /// This adds the clang `--target`, `-mcpu` (xtensa) or `-march`/`-mabi` (riscv) args
+    /// and the `-isystem` include directories of the gcc toolchain. It also sets the
+    /// linker and sysroot of this factory from `toolchain`, if they are not already set.
+    ///
+    /// If `triple` is not an ESP target or the gcc include directories cannot be
+    /// determined, these args are omitted and a warning is logged.
+    pub fn with_esp_target(mut self, triple: &str, toolchain: &ToolchainInfo) -> Self {
+        let mut args = match EspTarget::from_triple(triple) {
+            Some(target) => {
+                if self.mcu.is_none() {
+                    self.mcu = target.mcu().map(str::to_owned);
+                }
+                target.clang_args()
+            }
+            None => {
+                log::warn!(
+                    "Target '{triple}' is not a known ESP target, not adding any target clang args"
+                );
+                Vec::new()
+            }
+        };
+
+        if self.linker.is_none() {
+            self.linker = Some(toolchain.gcc.clone());
+        }
+        if self.sysroot.is_none() {
+            self.sysroot = toolchain.sysroot.clone();
+        }
+
+        match toolchain.gcc_include_dirs() {
+            Ok(dirs) => args.extend(dirs.iter().map(|dir| format!("-isystem{}", dir.display()))),
+            Err(err) => log::warn!(
+                "Could not determine the include directories of '{}', \
+                 not adding them to the clang args: {err:#}",
+                toolchain.gcc.display()
+            ),
+        }
+
+        log::debug!("Clang args derived for target '{triple}': {args:?}");
+
+        self.with_clang_args(args)
+    }
+
     /// Create a [`bindgen::Builder`] with these settings.
     pub fn builder(self) -> Result<bindgen::Builder> {
         self.create_builder(false)
@@ -162,6 +208,104 @@ impl Factory {
     }
 }
 
+/// The gcc toolchain used for an ESP target (see [`Factory::with_esp_target`]).
+#[derive(Clone, Debug)]
+pub struct ToolchainInfo {
+    /// The path of the toolchain's gcc executable (ex. `riscv32-esp-elf-gcc`).
+    pub gcc: PathBuf,
+    /// The sysroot of the toolchain. If `None` it is determined from `gcc`.
+    pub sysroot: Option<PathBuf>,
+}
+
+impl ToolchainInfo {
+    /// Create a toolchain info for the gcc executable `gcc`.
+    pub fn new(gcc: impl Into<PathBuf>) -> Self {
+        Self {
+            gcc: gcc.into(),
+            sysroot: None,
+        }
+    }
+
+    /// Set the sysroot of the toolchain.
+    #[must_use]
+    pub fn with_sysroot(mut self, sysroot: impl Into<PathBuf>) -> Self {
+        self.sysroot = Some(sysroot.into());
+        self
+    }
+
+    /// Get the include directories of gcc's own headers (ex. `stddef.h` and
+    /// `stdint.h`), which are not part of the sysroot.
+    pub fn gcc_include_dirs(&self) -> Result<Vec<PathBuf>> {
+        let dirs = ["include", "include-fixed"]
+            .iter()
+            .map(|name| {
+                cmd!(&self.gcc, format!("-print-file-name={name}"))
+                    .stdout()
+                    .map(PathBuf::from)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // gcc prints the name unchanged if it could not find the directory.
+        Ok(dirs.into_iter().filter(|dir| dir.is_absolute()).collect())
+    }
+}
+
+/// An ESP rust target.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum EspTarget {
+    /// An xtensa target with its mcu (ex. `esp32s3`).
+    Xtensa { mcu: String },
+    /// A riscv target with its ISA extensions (ex. `imac`).
+    Riscv { extensions: String },
+}
+
+impl EspTarget {
+    /// Parse the target from a rust target triple, returns `None` if `triple` is not an
+    /// ESP target.
+    fn from_triple(triple: &str) -> Option<Self> {
+        let mut parts = triple.split('-');
+        let arch = parts.next()?;
+        let vendor = parts.next()?;
+
+        if arch == "xtensa" && vendor.starts_with("esp") {
+            Some(Self::Xtensa {
+                mcu: vendor.to_owned(),
+            })
+        } else {
+            arch.strip_prefix("riscv32").map(|extensions| Self::Riscv {
+                extensions: extensions.to_owned(),
+            })
+        }
+    }
+
+    /// The mcu of this target, if it can be determined from the triple.
+    fn mcu(&self) -> Option<&str> {
+        match self {
+            Self::Xtensa { mcu } => Some(mcu),
+            Self::Riscv { .. } => None,
+        }
+    }
+
+    /// The clang args selecting this target.
+    fn clang_args(&self) -> Vec<String> {
+        match self {
+            Self::Xtensa { mcu } => vec!["--target=xtensa-esp-elf".into(), format!("-mcpu={mcu}")],
+            Self::Riscv { extensions } => {
+                let abi = if extensions.contains('f') {
+                    "ilp32f"
+                } else {
+                    "ilp32"
+                };
+                vec![
+                    "--target=riscv32-esp-elf".into(),
+                    format!("-march=rv32{extensions}"),
+                    format!("-mabi={abi}"),
+                ]
+            }
+        }
+    }
+}
+
 /// Get the default filename for bindings and set the environment variable named
 /// [`VAR_BINDINGS_FILE`] that is available during crate compilation to that path.
 pub fn default_bindings_file() -> Result<PathBuf> {
@@ -218,7 +362,6 @@ pub fn run_for_file(builder: bindgen::Builder, output_file: impl AsRef<Path>) ->
     Ok(())
 }
 
-/// Extension trait for [`bindgen::Builder`].
 /// Extension trait for [`bindgen::Builder`].
 pub trait BindgenExt: Sized {
     /// Add all input C/C++ headers using repeated [`bindgen::Builder::header`].
@@ -305,3 +448,35 @@ fn get_cpp_includes(sysroot: impl AsRef<Path>) -> Result<Vec<String>> {
         Ok(Vec::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn esp_target_clang_args() {
+        let args = |triple| EspTarget::from_triple(triple).map(|t| t.clang_args());
+
+        assert_eq!(
+            args("xtensa-esp32s3-espidf").unwrap(),
+            ["--target=xtensa-esp-elf", "-mcpu=esp32s3"]
+        );
+        assert_eq!(
+            args("xtensa-esp32-none-elf").unwrap(),
+            ["--target=xtensa-esp-elf", "-mcpu=esp32"]
+        );
+        assert_eq!(
+            args("riscv32imc-esp-espidf").unwrap(),
+            ["--target=riscv32-esp-elf", "-march=rv32imc", "-mabi=ilp32"]
+        );
+        assert_eq!(
+            args("riscv32imafc-esp-espidf").unwrap(),
+            [
+                "--target=riscv32-esp-elf",
+                "-march=rv32imafc",
+                "-mabi=ilp32f"
+            ]
+        );
+        assert_eq!(args("x86_64-unknown-linux-gnu"), None);
+    }
+}
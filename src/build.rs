@@ -13,6 +13,19 @@ use crate::cargo::{self, add_link_arg, print_warning, set_metadata, track_file};
 use crate::cli::{self, Arg, ArgDef};
 use crate::utils::OsStrExt;
 
+#[cfg(all(feature = "cmake", feature = "espidf"))]
+mod cmake_component;
+
+mod pkg_config;
+#[cfg(feature = "elf")]
+mod symbol_list;
+
+#[cfg(all(feature = "cmake", feature = "espidf"))]
+pub use cmake_component::*;
+pub use pkg_config::*;
+#[cfg(feature = "elf")]
+pub use symbol_list::*;
+
 const C_INCLUDE_ARGS_VAR: &str = "EMBUILD_C_INCLUDE_ARGS";
 const LINK_ARGS_VAR: &str = "EMBUILD_LINK_ARGS";
 const CFG_ARGS_VAR: &str = "EMBUILD_CFG_ARGS";
@@ -37,6 +50,12 @@ pub const LDPROXY_LINKER_ARG: ArgDef = Arg::option("ldproxy-linker").long();
 pub const LDPROXY_DEDUP_LIBS_ARG: ArgDef = Arg::flag("ldproxy-dedup-libs").long();
 /// The `--ldproxy-cwd` argument definition.
 pub const LDPROXY_WORKING_DIRECTORY_ARG: ArgDef = Arg::option("ldproxy-cwd").long();
+/// The `--ldproxy-emit-command-file` argument definition.
+pub const LDPROXY_EMIT_COMMAND_FILE_ARG: ArgDef = Arg::option("ldproxy-emit-command-file").long();
+/// The `--ldproxy-dry-run` argument definition.
+pub const LDPROXY_DRY_RUN_ARG: ArgDef = Arg::flag("ldproxy-dry-run").long();
+/// The `--ldproxy-color` argument definition.
+pub const LDPROXY_COLOR_ARG: ArgDef = Arg::option("ldproxy-color").long();
 
 pub fn env_options_iter(
     env_var_prefix: impl AsRef<str>,
@@ -279,6 +298,58 @@ impl LinkArgsBuilder {
     }
 }
 
+/// A static native library that a crate should be linked against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NativeLib {
+    /// The name of the library as passed to the linker (ex. `foo` for `libfoo.a`).
+    pub name: String,
+    /// The path to the library file.
+    pub path: PathBuf,
+}
+
+impl NativeLib {
+    /// Create a native library from the path to `lib<name>.a`.
+    ///
+    /// Returns [`None`] if the file name is not of this form.
+    pub fn from_path(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .and_then(OsStr::to_str)?
+            .strip_prefix("lib")?
+            .strip_suffix(".a")
+            .filter(|name| !name.is_empty())?
+            .to_owned();
+
+        Some(Self { name, path })
+    }
+
+    /// The directory containing the library file.
+    pub fn search_dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
+    }
+
+    /// Link this crate against the library by adding its search directory and the
+    /// library itself.
+    pub fn output(&self) {
+        cargo::add_link_search_path(self.search_dir());
+        cargo::add_link_lib(format_args!("static={}", self.name));
+    }
+
+    /// The linker arguments for linking against all `libs` in a single link group
+    /// (`-Wl,--start-group <libs..> -Wl,--end-group`), so that libraries which depend on
+    /// each other cyclically can be linked regardless of their order.
+    pub fn link_group(libs: &[NativeLib]) -> Result<LinkArgs> {
+        let mut args = vec!["-Wl,--start-group".to_owned()];
+        for lib in libs {
+            args.push(lib.path.try_to_str()?.to_owned());
+        }
+        args.push("-Wl,--end-group".to_owned());
+
+        Ok(LinkArgs { args })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LinkArgs {
     pub args: Vec<String>,
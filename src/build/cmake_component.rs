@@ -0,0 +1,337 @@
+//! Building esp-idf components as static libraries with cmake.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::NativeLib;
+use crate::cargo::{self, track_file};
+use crate::cmake::file_api::codemodel::target;
+use crate::cmake::file_api::ObjKind;
+use crate::cmake::{self, Generator, Query};
+use crate::cmd;
+use crate::espidf::{EspIdfVersion, IDF_PATH_VAR};
+
+/// The cmake file-api client name used for querying the built libraries.
+const QUERY_CLIENT_NAME: &str = "embuild-component";
+
+/// Build a cmake project using an esp-idf component as a static library.
+///
+/// This wraps the whole flow of configuring and building the cmake project in
+/// `source_dir`, discovering all static libraries it produced and linking the crate of
+/// the running build script against them.
+///
+/// As esp-idf component libraries depend on each other cyclically, they are linked in a
+/// single link group (see [`NativeLib::link_group`]) with `cargo:rustc-link-arg`, which
+/// only applies to the binaries of the crate. Crates linking the libraries into the
+/// binaries of their dependents must [`propagate`](super::LinkArgs::propagate) that link
+/// group instead.
+///
+/// ```ignore
+/// let libs = embuild::build::CmakeComponentLibrary::new(Path::new("component"))
+///     .mcu("esp32c3")
+///     .idf_version_requirement(">=5.0")
+///     .build()?;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CmakeComponentLibrary;
+
+impl CmakeComponentLibrary {
+    /// Create a builder for the cmake project in `source_dir`.
+    ///
+    /// The project must be an esp-idf project (i.e. include
+    /// `$ENV{IDF_PATH}/tools/cmake/project.cmake`).
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(source_dir: &Path) -> CmakeComponentLibraryBuilder {
+        CmakeComponentLibraryBuilder {
+            source_dir: source_dir.to_owned(),
+            build_dir: None,
+            idf_path: None,
+            idf_version_requirement: None,
+            mcu: None,
+            sdkconfig_defaults: Vec::new(),
+            defines: Vec::new(),
+            extra_components: Vec::new(),
+            generator: Generator::Ninja,
+            env_path: None,
+        }
+    }
+}
+
+/// A builder for configuring and building a [`CmakeComponentLibrary`].
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct CmakeComponentLibraryBuilder {
+    source_dir: PathBuf,
+    build_dir: Option<PathBuf>,
+    idf_path: Option<PathBuf>,
+    idf_version_requirement: Option<String>,
+    mcu: Option<String>,
+    sdkconfig_defaults: Vec<PathBuf>,
+    defines: Vec<(String, String)>,
+    extra_components: Vec<PathBuf>,
+    generator: Generator,
+    env_path: Option<OsString>,
+}
+
+impl CmakeComponentLibraryBuilder {
+    /// The cmake build directory.
+    ///
+    /// If not set, `<out dir>/cmake-component` is used (see [`cargo::out_dir`]).
+    pub fn build_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.build_dir = Some(dir.into());
+        self
+    }
+
+    /// The path to the esp-idf source tree.
+    ///
+    /// If not set, the value of the `IDF_PATH` environment variable is used.
+    pub fn idf_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.idf_path = Some(path.into());
+        self
+    }
+
+    /// A requirement the version of the esp-idf must satisfy (see
+    /// [`EspIdfVersion::satisfies`]), ex. `>=4.4, <5.2`.
+    pub fn idf_version_requirement(mut self, requirement: impl Into<String>) -> Self {
+        self.idf_version_requirement = Some(requirement.into());
+        self
+    }
+
+    /// The MCU to build for (passed as `IDF_TARGET`).
+    ///
+    /// If not set, the esp-idf's default is used.
+    pub fn mcu(mut self, mcu: impl Into<String>) -> Self {
+        self.mcu = Some(mcu.into());
+        self
+    }
+
+    /// Add sdkconfig defaults files (passed as `SDKCONFIG_DEFAULTS`).
+    pub fn sdkconfig_defaults(
+        mut self,
+        files: impl IntoIterator<Item = impl Into<PathBuf>>,
+    ) -> Self {
+        self.sdkconfig_defaults
+            .extend(files.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add a cmake cache definition `-D<name>=<value>` (ex. `CMAKE_BUILD_TYPE`).
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add directories of extra components (passed as `EXTRA_COMPONENT_DIRS`).
+    pub fn extra_components(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.extra_components
+            .extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// The cmake generator to use, [`Generator::Ninja`] by default.
+    pub fn generator(mut self, generator: Generator) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    /// The `PATH` environment variable containing all tools required by the esp-idf
+    /// (ex. [`EspIdf::exported_path`](crate::espidf::EspIdf::exported_path)).
+    pub fn env_path(mut self, path: impl Into<OsString>) -> Self {
+        self.env_path = Some(path.into());
+        self
+    }
+
+    /// Configure and build the cmake project, then link this crate against all static
+    /// libraries it produced (in a link group) and return them.
+    pub fn build(self) -> Result<Vec<NativeLib>> {
+        let idf_path = self
+            .idf_path
+            .clone()
+            .or_else(|| env::var_os(IDF_PATH_VAR).map(PathBuf::from))
+            .ok_or_else(|| {
+                anyhow!("No esp-idf path given and environment variable `{IDF_PATH_VAR}` not set")
+            })?;
+
+        if let Some(requirement) = &self.idf_version_requirement {
+            let version = EspIdfVersion::try_from(&idf_path)?;
+            if !version.satisfies(requirement)? {
+                bail!(
+                    "esp-idf version {version} at '{}' does not satisfy requirement '{requirement}'",
+                    idf_path.display()
+                );
+            }
+        }
+
+        let build_dir = self
+            .build_dir
+            .clone()
+            .unwrap_or_else(|| cargo::out_dir().join("cmake-component"));
+        let query = Query::new(&build_dir, QUERY_CLIENT_NAME, &[ObjKind::Codemodel])?;
+
+        let mut configure = cmd!(
+            cmake::cmake(), "-S", &self.source_dir, "-B", &build_dir, "-G", self.generator.name();
+            env=(IDF_PATH_VAR, &idf_path), args=(self.definitions()?)
+        );
+        let mut build = cmd!(cmake::cmake(), "--build", &build_dir; env=(IDF_PATH_VAR, &idf_path));
+        if let Some(path) = &self.env_path {
+            configure.env("PATH", path);
+            build.env("PATH", path);
+        }
+
+        configure.run().with_context(|| {
+            anyhow!(
+                "Failed to configure cmake project '{}'",
+                self.source_dir.display()
+            )
+        })?;
+        build.run().with_context(|| {
+            anyhow!(
+                "Failed to build cmake project '{}'",
+                self.source_dir.display()
+            )
+        })?;
+
+        let codemodel = query.get_replies()?.get_codemodel()?;
+        let top_build_dir = codemodel.paths.build.clone();
+
+        let mut libs = Vec::new();
+        for target in codemodel.into_first_conf().targets() {
+            let target = target?;
+            if target.target_type != target::Type::StaticLibrary {
+                continue;
+            }
+
+            libs.extend(
+                target.artifacts.iter().filter_map(|artifact| {
+                    NativeLib::from_path(top_build_dir.join(&artifact.path))
+                }),
+            );
+        }
+
+        if libs.is_empty() {
+            bail!(
+                "cmake project '{}' did not produce any static libraries",
+                self.source_dir.display()
+            );
+        }
+
+        track_file(&self.source_dir);
+        for file in &self.sdkconfig_defaults {
+            track_file(file);
+        }
+        NativeLib::link_group(&libs)?.output();
+
+        Ok(libs)
+    }
+
+    /// All `-D<name>=<value>` cmake cache definitions for the configure step.
+    fn definitions(&self) -> Result<Vec<String>> {
+        let mut defines = Vec::new();
+
+        if let Some(mcu) = &self.mcu {
+            defines.push(format!("-DIDF_TARGET={mcu}"));
+        }
+        if !self.sdkconfig_defaults.is_empty() {
+            defines.push(format!(
+                "-DSDKCONFIG_DEFAULTS={}",
+                join_cmake_list(&self.sdkconfig_defaults)?
+            ));
+        }
+        if !self.extra_components.is_empty() {
+            defines.push(format!(
+                "-DEXTRA_COMPONENT_DIRS={}",
+                join_cmake_list(&self.extra_components)?
+            ));
+        }
+        defines.extend(
+            self.defines
+                .iter()
+                .map(|(name, value)| format!("-D{name}={value}")),
+        );
+
+        Ok(defines)
+    }
+}
+
+/// Join `paths` to a `;`-separated cmake list using forward slashes.
+fn join_cmake_list(paths: &[PathBuf]) -> Result<String> {
+    use crate::utils::OsStrExt;
+
+    Ok(paths
+        .iter()
+        .map(|p| p.try_to_str().map(|s| s.replace('\\', "/")))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configure_definitions() {
+        let builder = CmakeComponentLibrary::new(Path::new("component"))
+            .mcu("esp32c3")
+            .sdkconfig_defaults(["sdkconfig.defaults", "C:\\project\\sdkconfig.release"])
+            .extra_components([PathBuf::from("../components/a"), PathBuf::from("b")])
+            .define("CMAKE_BUILD_TYPE", "Release");
+
+        assert_eq!(
+            builder.definitions().unwrap(),
+            [
+                "-DIDF_TARGET=esp32c3",
+                "-DSDKCONFIG_DEFAULTS=sdkconfig.defaults;C:/project/sdkconfig.release",
+                "-DEXTRA_COMPONENT_DIRS=../components/a;b",
+                "-DCMAKE_BUILD_TYPE=Release",
+            ]
+        );
+
+        assert!(CmakeComponentLibrary::new(Path::new("component"))
+            .definitions()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn cmake_list() {
+        assert_eq!(
+            join_cmake_list(&[PathBuf::from("a\\b\\c"), PathBuf::from("/d/e")]).unwrap(),
+            "a/b/c;/d/e"
+        );
+        assert_eq!(join_cmake_list(&[]).unwrap(), "");
+    }
+
+    #[test]
+    fn native_lib_from_path() {
+        let lib = NativeLib::from_path("build/esp-idf/main/libmain.a").unwrap();
+        assert_eq!(lib.name, "main");
+        assert_eq!(lib.search_dir(), Path::new("build/esp-idf/main"));
+
+        assert_eq!(
+            NativeLib::from_path("libfoo.a").unwrap().search_dir(),
+            Path::new("")
+        );
+        assert_eq!(NativeLib::from_path("build/lib.a"), None);
+        assert_eq!(NativeLib::from_path("build/main.a"), None);
+        assert_eq!(NativeLib::from_path("build/libmain.so"), None);
+    }
+
+    #[test]
+    fn native_lib_link_group() {
+        let libs = ["build/esp-idf/main/libmain.a", "build/esp-idf/log/liblog.a"]
+            .map(|path| NativeLib::from_path(path).unwrap());
+
+        assert_eq!(
+            NativeLib::link_group(&libs).unwrap().args,
+            [
+                "-Wl,--start-group",
+                "build/esp-idf/main/libmain.a",
+                "build/esp-idf/log/liblog.a",
+                "-Wl,--end-group"
+            ]
+        );
+    }
+}
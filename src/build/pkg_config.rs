@@ -0,0 +1,239 @@
+//! Locating native libraries with pkg-config.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::CInclArgs;
+use crate::cargo::{self, track_env_var, track_file};
+use crate::cli::UnixCommandArgs;
+use crate::cmd;
+
+const PKG_CONFIG_VAR: &str = "PKG_CONFIG";
+const PKG_CONFIG_PATH_VAR: &str = "PKG_CONFIG_PATH";
+const PKG_CONFIG_LIBDIR_VAR: &str = "PKG_CONFIG_LIBDIR";
+const PKG_CONFIG_SYSROOT_DIR_VAR: &str = "PKG_CONFIG_SYSROOT_DIR";
+
+/// Search for native libraries using the `.pc` files in a set of directories.
+///
+/// By default the sysroot of the target is taken from `PKG_CONFIG_SYSROOT_DIR`. If a
+/// sysroot is set, it is prepended to all include and library directories by
+/// pkg-config, and the default pkg-config directories of the host are replaced by the
+/// ones of the sysroot (unless `PKG_CONFIG_LIBDIR` is set), so that no host libraries
+/// are found when cross-compiling.
+///
+/// ```ignore
+/// let info = PkgConfigSearch::new(vec![component_install_dir.join("lib/pkgconfig")])
+///     .probe("mbedtls", Some(">= 3.0"))?;
+/// info.emit()?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[must_use]
+pub struct PkgConfigSearch {
+    pkg_config_path: Vec<PathBuf>,
+    sysroot_dir: Option<PathBuf>,
+}
+
+impl PkgConfigSearch {
+    /// Create a search in the directories `pkg_config_path`, which precede the
+    /// directories already in `PKG_CONFIG_PATH`.
+    pub fn new(pkg_config_path: Vec<PathBuf>) -> Self {
+        Self {
+            pkg_config_path,
+            sysroot_dir: env::var_os(PKG_CONFIG_SYSROOT_DIR_VAR)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+
+    /// Use `dir` as the sysroot of the target instead of `PKG_CONFIG_SYSROOT_DIR`.
+    pub fn with_sysroot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.sysroot_dir = Some(dir.into());
+        self
+    }
+
+    /// Find the package `name` and get the flags needed to use it.
+    ///
+    /// `version_req` is a pkg-config version constraint (ex. `>= 1.2`); a plain
+    /// version is treated as a minimum version.
+    pub fn probe(&self, name: &str, version_req: Option<&str>) -> Result<PkgConfigInfo> {
+        let module = match version_req.map(str::trim) {
+            Some(req) if req.starts_with(|c: char| c.is_ascii_digit()) => {
+                format!("{name} >= {req}")
+            }
+            Some(req) if !req.is_empty() => format!("{name} {req}"),
+            _ => name.to_owned(),
+        };
+
+        for var in [
+            PKG_CONFIG_VAR,
+            PKG_CONFIG_PATH_VAR,
+            PKG_CONFIG_LIBDIR_VAR,
+            PKG_CONFIG_SYSROOT_DIR_VAR,
+        ] {
+            track_env_var(var);
+        }
+        for dir in &self.pkg_config_path {
+            track_file(dir);
+        }
+
+        let pkg_config = env::var_os(PKG_CONFIG_VAR)
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| "pkg-config".into());
+
+        let mut cmd = cmd!(pkg_config, "--cflags", "--libs", &module;
+            env=(PKG_CONFIG_PATH_VAR, self.search_path()?));
+        if let Some(sysroot_dir) = &self.sysroot_dir {
+            cmd.env(PKG_CONFIG_SYSROOT_DIR_VAR, sysroot_dir);
+            if env::var_os(PKG_CONFIG_LIBDIR_VAR).is_none() {
+                cmd.env(PKG_CONFIG_LIBDIR_VAR, sysroot_libdir(sysroot_dir)?);
+            }
+        }
+
+        let output = cmd
+            .stdout()
+            .with_context(|| anyhow!("Failed to find package '{module}' with pkg-config"))?;
+
+        Ok(PkgConfigInfo::parse(&output))
+    }
+
+    /// The value of `PKG_CONFIG_PATH` passed to pkg-config.
+    fn search_path(&self) -> Result<OsString> {
+        let existing = env::var_os(PKG_CONFIG_PATH_VAR).unwrap_or_default();
+
+        env::join_paths(
+            self.pkg_config_path
+                .iter()
+                .cloned()
+                .chain(env::split_paths(&existing)),
+        )
+        .context("Invalid pkg-config search path")
+    }
+}
+
+/// The default pkg-config directories of the sysroot `dir`.
+fn sysroot_libdir(dir: &Path) -> Result<OsString> {
+    env::join_paths([
+        dir.join("usr").join("lib").join("pkgconfig"),
+        dir.join("usr").join("share").join("pkgconfig"),
+    ])
+    .context("Invalid pkg-config sysroot")
+}
+
+/// The flags needed to compile and link against a package found by [`PkgConfigSearch`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PkgConfigInfo {
+    /// The include directories (`-I<dir>`).
+    pub include_dirs: Vec<PathBuf>,
+    /// The libraries to link (`-l<lib>`).
+    pub link_libs: Vec<String>,
+    /// The library search directories (`-L<dir>`).
+    pub link_dirs: Vec<PathBuf>,
+    /// The preprocessor defines (`-D<name>=<value>`), the value is empty if the define
+    /// has none.
+    pub defines: Vec<(String, String)>,
+}
+
+impl PkgConfigInfo {
+    /// Print the cargo directives for linking against the package, and set the
+    /// [`CInclArgs`] of the include directories and defines as metadata for dependent
+    /// crates.
+    pub fn emit(&self) -> Result<()> {
+        for dir in &self.link_dirs {
+            dir.to_str().ok_or_else(|| {
+                anyhow!("Library directory '{}' is not valid UTF-8", dir.display())
+            })?;
+            cargo::add_link_search_path(dir);
+        }
+        for lib in &self.link_libs {
+            cargo::add_link_lib(lib);
+        }
+        if !self.include_dirs.is_empty() || !self.defines.is_empty() {
+            CInclArgs::from(self).propagate();
+        }
+
+        Ok(())
+    }
+
+    /// Parse the output of `pkg-config --cflags --libs`.
+    ///
+    /// Flags other than include directories, defines, libraries and library directories
+    /// are ignored.
+    fn parse(output: &str) -> Self {
+        let mut info = Self::default();
+
+        for arg in UnixCommandArgs::new(output) {
+            if let Some(dir) = arg.strip_prefix("-I") {
+                info.include_dirs.push(dir.into());
+            } else if let Some(dir) = arg.strip_prefix("-L") {
+                info.link_dirs.push(dir.into());
+            } else if let Some(lib) = arg.strip_prefix("-l") {
+                info.link_libs.push(lib.to_owned());
+            } else if let Some(define) = arg.strip_prefix("-D") {
+                let (name, value) = define.split_once('=').unwrap_or((define, ""));
+                info.defines.push((name.to_owned(), value.to_owned()));
+            } else {
+                log::debug!("Ignoring pkg-config flag '{arg}'");
+            }
+        }
+
+        info
+    }
+}
+
+impl From<&PkgConfigInfo> for CInclArgs {
+    fn from(info: &PkgConfigInfo) -> Self {
+        let args = info
+            .defines
+            .iter()
+            .map(|(name, value)| {
+                if value.is_empty() {
+                    format!("-D{name}")
+                } else {
+                    format!("-D{name}={value}")
+                }
+            })
+            .chain(
+                info.include_dirs
+                    .iter()
+                    .map(|dir| format!("\"-isystem{}\"", dir.display())),
+            )
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Self { args }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pkg_config_output() {
+        let info = PkgConfigInfo::parse(
+            "-DMBEDTLS_CONFIG_FILE=\\\"config.h\\\" -DUSE_FOO -I/sysroot/usr/include \
+             -I/opt/my\\ dir/include -pthread -L/sysroot/usr/lib -lmbedtls -lmbedx509",
+        );
+
+        assert_eq!(
+            info,
+            PkgConfigInfo {
+                include_dirs: vec!["/sysroot/usr/include".into(), "/opt/my dir/include".into()],
+                link_libs: vec!["mbedtls".into(), "mbedx509".into()],
+                link_dirs: vec!["/sysroot/usr/lib".into()],
+                defines: vec![
+                    ("MBEDTLS_CONFIG_FILE".into(), "\"config.h\"".into()),
+                    ("USE_FOO".into(), "".into())
+                ],
+            }
+        );
+        assert_eq!(
+            CInclArgs::from(&info).args,
+            "-DMBEDTLS_CONFIG_FILE=\"config.h\" -DUSE_FOO \"-isystem/sysroot/usr/include\" \
+             \"-isystem/opt/my dir/include\""
+        );
+    }
+}
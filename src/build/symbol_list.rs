@@ -0,0 +1,358 @@
+//! Listing the symbols of static libraries.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::str;
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use xmas_elf::sections::{SectionData, ShType};
+use xmas_elf::symbol_table::{self, Binding, Entry};
+use xmas_elf::ElfFile;
+
+use crate::cargo::add_link_arg;
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_LEN: usize = 60;
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// The maximum alignment of the ELF structures.
+const ELF_ALIGN: usize = 8;
+
+/// The global symbols a static library (`.a` archive) defines and the ones it expects
+/// the linker to provide.
+///
+/// This can be used to advertise to a C project which symbols a rust `staticlib` needs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolList {
+    /// All global and weak symbols defined by any object file of the archive.
+    pub defined: BTreeSet<String>,
+    /// All symbols referenced by an object file of the archive that are not defined
+    /// by any object file of the archive.
+    pub undefined: BTreeSet<String>,
+}
+
+impl SymbolList {
+    /// Read the symbols of all ELF object files contained in the archive `path`.
+    ///
+    /// Archive members that are not ELF files are ignored.
+    pub fn from_archive(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .with_context(|| anyhow!("Failed to read archive '{}'", path.display()))?;
+
+        Self::parse_archive(&data)
+            .with_context(|| anyhow!("Failed to read symbols of archive '{}'", path.display()))
+    }
+
+    /// Add a `--undefined=<symbol>` linker argument for every undefined symbol, so that
+    /// the linker pulls in the object files providing them.
+    ///
+    /// Assumes a gcc-like linker flavor, the arguments are passed as `-Wl,--undefined=..`.
+    pub fn emit_link_args_for_undefined(&self) {
+        for symbol in &self.undefined {
+            add_link_arg(format_args!("-Wl,--undefined={symbol}"));
+        }
+    }
+
+    fn parse_archive(data: &[u8]) -> Result<Self> {
+        let mut list = Self::default();
+        let mut referenced = BTreeSet::new();
+
+        for (name, member) in archive_members(data)? {
+            if !member.starts_with(ELF_MAGIC) {
+                continue;
+            }
+
+            // The ELF parser requires the data to be aligned to the ELF structures, but
+            // archive members are only 2-byte aligned, so copy it to an aligned offset.
+            let mut buffer = vec![0; member.len() + ELF_ALIGN];
+            let offset = buffer.as_ptr().align_offset(ELF_ALIGN);
+            let aligned = &mut buffer[offset..offset + member.len()];
+            aligned.copy_from_slice(member);
+
+            list.add_object(aligned, &mut referenced)
+                .with_context(|| anyhow!("Failed to read symbols of member '{name}'"))?;
+        }
+
+        list.undefined = referenced.difference(&list.defined).cloned().collect();
+        Ok(list)
+    }
+
+    /// Add the symbols defined by the ELF object `data` and collect the symbols it
+    /// references in `referenced`.
+    fn add_object(&mut self, data: &[u8], referenced: &mut BTreeSet<String>) -> Result<()> {
+        let elf = ElfFile::new(data).map_err(Error::msg)?;
+
+        for header in elf.section_iter() {
+            if header.get_type().map_err(Error::msg)? != ShType::SymTab {
+                continue;
+            }
+
+            match header.get_data(&elf).map_err(Error::msg)? {
+                SectionData::SymbolTable32(entries) => {
+                    self.add_symbols(&elf, entries, referenced)?
+                }
+                SectionData::SymbolTable64(entries) => {
+                    self.add_symbols(&elf, entries, referenced)?
+                }
+                _ => bail!("Invalid symbol table section"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_symbols(
+        &mut self,
+        elf: &ElfFile,
+        entries: &[impl Entry],
+        referenced: &mut BTreeSet<String>,
+    ) -> Result<()> {
+        for entry in entries {
+            let binding = entry.get_binding().map_err(Error::msg)?;
+            if binding != Binding::Global && binding != Binding::Weak {
+                continue;
+            }
+            if matches!(
+                entry.get_type().map_err(Error::msg)?,
+                symbol_table::Type::Section | symbol_table::Type::File
+            ) {
+                continue;
+            }
+
+            let name = entry.get_name(elf).map_err(Error::msg)?;
+            if name.is_empty() {
+                continue;
+            }
+
+            // `SHN_UNDEF`
+            if entry.shndx() == 0 {
+                referenced.insert(name.to_owned());
+            } else {
+                self.defined.insert(name.to_owned());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the name and contents of all file members of the `ar` archive `data`.
+///
+/// Supports the GNU and BSD variants of long member names.
+fn archive_members(data: &[u8]) -> Result<Vec<(String, &[u8])>> {
+    let mut rest = data
+        .strip_prefix(AR_MAGIC)
+        .ok_or_else(|| anyhow!("Not an ar archive"))?;
+    let mut long_names: &[u8] = &[];
+    let mut members = Vec::new();
+
+    while !rest.is_empty() {
+        if rest.len() < AR_HEADER_LEN || &rest[58..60] != b"`\n" {
+            bail!("Invalid ar member header");
+        }
+        let (header, after_header) = rest.split_at(AR_HEADER_LEN);
+
+        let size: usize = str::from_utf8(&header[48..58])?
+            .trim()
+            .parse()
+            .context("Invalid ar member size")?;
+        if after_header.len() < size {
+            bail!("Truncated ar member");
+        }
+        let (mut contents, after_contents) = after_header.split_at(size);
+        // Members are padded to an even size.
+        rest = after_contents
+            .strip_prefix(b"\n")
+            .filter(|_| size % 2 == 1)
+            .unwrap_or(after_contents);
+
+        let raw_name = str::from_utf8(&header[..16])?.trim_end();
+        let name = match raw_name {
+            // GNU symbol table
+            "/" | "/SYM64/" => continue,
+            // GNU long names table
+            "//" => {
+                long_names = contents;
+                continue;
+            }
+            _ if raw_name.starts_with("#1/") => {
+                // BSD: the name is stored at the start of the contents
+                let len: usize = raw_name[3..].parse().context("Invalid ar member name")?;
+                if contents.len() < len {
+                    bail!("Truncated ar member name");
+                }
+                let (name, member) = contents.split_at(len);
+                contents = member;
+                str::from_utf8(name)?.trim_end_matches('\0').to_owned()
+            }
+            _ if raw_name.starts_with('/') => {
+                // GNU: the name is stored at an offset into the long names table
+                let offset: usize = raw_name[1..].parse().context("Invalid ar member name")?;
+                let name = long_names
+                    .get(offset..)
+                    .ok_or_else(|| anyhow!("Invalid ar long member name offset"))?;
+                let end = name
+                    .windows(2)
+                    .position(|w| w == b"/\n")
+                    .unwrap_or(name.len());
+                str::from_utf8(&name[..end])?.to_owned()
+            }
+            _ => raw_name.trim_end_matches('/').to_owned(),
+        };
+
+        if name == "__.SYMDEF" || name == "__.SYMDEF SORTED" {
+            continue;
+        }
+        members.push((name, contents));
+    }
+
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a minimal 32-bit little-endian ELF relocatable object with the global
+    /// symbols `defined` and `undefined`.
+    fn elf_object(defined: &[&str], undefined: &[&str]) -> Vec<u8> {
+        fn u16le(out: &mut Vec<u8>, v: u16) {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        fn u32le(out: &mut Vec<u8>, v: u32) {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; 16];
+        for (name, shndx) in defined
+            .iter()
+            .map(|n| (n, 1))
+            .chain(undefined.iter().map(|n| (n, 0)))
+        {
+            u32le(&mut symtab, strtab.len() as u32);
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+            u32le(&mut symtab, 0); // value
+            u32le(&mut symtab, 0); // size
+            symtab.push(0x10); // STB_GLOBAL, STT_NOTYPE
+            symtab.push(0); // STV_DEFAULT
+            u16le(&mut symtab, shndx);
+        }
+        while strtab.len() % 4 != 0 {
+            strtab.push(0);
+        }
+        let shstrtab = b"\0.strtab\0.symtab\0.shstrtab\0\0";
+
+        let strtab_off = 52;
+        let symtab_off = strtab_off + strtab.len();
+        let shstrtab_off = symtab_off + symtab.len();
+        let sh_off = shstrtab_off + shstrtab.len();
+
+        let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+        elf.resize(16, 0);
+        u16le(&mut elf, 1); // ET_REL
+        u16le(&mut elf, 94); // EM_XTENSA
+        u32le(&mut elf, 1);
+        u32le(&mut elf, 0);
+        u32le(&mut elf, 0);
+        u32le(&mut elf, sh_off as u32);
+        u32le(&mut elf, 0);
+        for v in [52, 0, 0, 40, 4, 3] {
+            u16le(&mut elf, v);
+        }
+        elf.extend_from_slice(&strtab);
+        elf.extend_from_slice(&symtab);
+        elf.extend_from_slice(shstrtab);
+
+        let sections = [
+            [0; 10],
+            [1, 3, 0, 0, strtab_off, strtab.len(), 0, 0, 1, 0],
+            [9, 2, 0, 0, symtab_off, symtab.len(), 1, 1, 4, 16],
+            [17, 3, 0, 0, shstrtab_off, shstrtab.len(), 0, 0, 1, 0],
+        ];
+        for section in sections {
+            for v in section {
+                u32le(&mut elf, v as u32);
+            }
+        }
+
+        elf
+    }
+
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut long_names = Vec::new();
+        let mut data = AR_MAGIC.to_vec();
+
+        let push_member = |data: &mut Vec<u8>, name: &str, contents: &[u8]| {
+            data.extend_from_slice(
+                format!("{name:<16}{:<32}{:<10}`\n", 0, contents.len()).as_bytes(),
+            );
+            data.extend_from_slice(contents);
+            if contents.len() % 2 == 1 {
+                data.push(b'\n');
+            }
+        };
+
+        let names = members
+            .iter()
+            .map(|(name, _)| {
+                if name.len() < 16 {
+                    format!("{name}/")
+                } else {
+                    let offset = long_names.len();
+                    long_names.extend_from_slice(name.as_bytes());
+                    long_names.extend_from_slice(b"/\n");
+                    format!("/{offset}")
+                }
+            })
+            .collect::<Vec<_>>();
+
+        push_member(&mut data, "/", &[0, 0, 0, 0]);
+        if !long_names.is_empty() {
+            push_member(&mut data, "//", &long_names);
+        }
+        for (name, (_, contents)) in names.iter().zip(members) {
+            push_member(&mut data, name, contents);
+        }
+
+        data
+    }
+
+    #[test]
+    fn archive_symbols() {
+        let lib_o = elf_object(&["rust_main", "rust_helper"], &["esp_log_write", "malloc"]);
+        let alloc_o = elf_object(&["malloc"], &["rust_helper", "abort"]);
+        let data = archive(&[
+            ("lib-0123456789abcdef.rust_main.o", &lib_o),
+            ("alloc.o", &alloc_o),
+            ("README.txt", b"not an object file"),
+        ]);
+
+        let list = SymbolList::parse_archive(&data).unwrap();
+
+        assert_eq!(
+            list.defined.iter().collect::<Vec<_>>(),
+            ["malloc", "rust_helper", "rust_main"]
+        );
+        assert_eq!(
+            list.undefined.iter().collect::<Vec<_>>(),
+            ["abort", "esp_log_write"]
+        );
+    }
+
+    #[test]
+    fn archive_member_names() {
+        let data = archive(&[("a.o", b"a"), ("a_very_long_member_name.o", b"bc")]);
+
+        let members = archive_members(&data).unwrap();
+        assert_eq!(
+            members,
+            [
+                ("a.o".to_owned(), &b"a"[..]),
+                ("a_very_long_member_name.o".to_owned(), &b"bc"[..])
+            ]
+        );
+        assert!(archive_members(b"not an archive").is_err());
+    }
+}
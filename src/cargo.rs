@@ -1,11 +1,13 @@
 //! Utils for interacting with cargo.
 
-use std::ffi::OsStr;
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{env, fs};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 #[cfg(feature = "manifest")]
 use cargo_toml::{Manifest, Product};
 use log::*;
@@ -320,6 +322,22 @@ pub fn add_link_arg(arg: impl Display) {
     println!("cargo:rustc-link-arg={arg}");
 }
 
+/// Add a directory to the native library search path of this package's linker
+/// invocation.
+pub fn add_link_search_path(dir: impl AsRef<Path>) {
+    println!(
+        "cargo:rustc-link-search=native={}",
+        dir.as_ref().try_to_str().unwrap()
+    );
+}
+
+/// Link this package against the native library `lib`.
+///
+/// `lib` may be prefixed with the kind of library (ex. `static=foo`).
+pub fn add_link_lib(lib: impl Display) {
+    println!("cargo:rustc-link-lib={lib}");
+}
+
 /// Rerun this build script if the file or directory has changed.
 pub fn track_file(file_or_dir: impl AsRef<Path>) {
     println!(
@@ -367,6 +385,205 @@ pub fn out_dir() -> PathBuf {
         .into()
 }
 
+/// The prefix of the environment variables cargo sets for every enabled feature when
+/// running a build script.
+const CARGO_FEATURE_VAR_PREFIX: &str = "CARGO_FEATURE_";
+
+/// The set of cargo features enabled for the package whose build script is currently
+/// running.
+///
+/// Cargo communicates enabled features to build scripts through `CARGO_FEATURE_<name>`
+/// environment variables, where `<name>` is the feature name converted to uppercase
+/// and with `-` translated to `_`. Because of this translation the original spelling of
+/// a feature can't be recovered, so all feature names are stored normalized: in
+/// lowercase and with every `-` replaced by `_`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeatureSet {
+    features: BTreeSet<String>,
+}
+
+impl FeatureSet {
+    /// Collect all features enabled through `CARGO_FEATURE_*` environment variables.
+    pub fn from_env() -> Self {
+        Self::from_vars(env::vars_os())
+    }
+
+    fn from_vars(vars: impl IntoIterator<Item = (OsString, OsString)>) -> Self {
+        let features = vars
+            .into_iter()
+            .filter_map(|(key, _)| {
+                key.to_str()?
+                    .strip_prefix(CARGO_FEATURE_VAR_PREFIX)
+                    .filter(|name| !name.is_empty())
+                    .map(normalize_feature_name)
+            })
+            .collect();
+
+        Self { features }
+    }
+
+    /// Whether `feature` is enabled.
+    ///
+    /// `feature` may be given in any case and either with `-` or `_` (ex. `foo-bar`,
+    /// `foo_bar` and `FOO_BAR` are all the same feature).
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.features.contains(&normalize_feature_name(feature))
+    }
+
+    /// Iterate over the normalized names of all enabled features in alphabetical order.
+    pub fn all_enabled(&self) -> impl Iterator<Item = &str> {
+        self.features.iter().map(String::as_str)
+    }
+
+    /// Whether at least one of `features` is enabled.
+    pub fn any_of(&self, features: &[&str]) -> bool {
+        features.iter().any(|feature| self.is_enabled(feature))
+    }
+}
+
+/// Normalize a cargo feature name the same way cargo does for `CARGO_FEATURE_<name>`,
+/// but lowercased.
+fn normalize_feature_name(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+/// Get the normalized names of all features enabled for the package whose build script is
+/// currently running (see [`FeatureSet`]).
+pub fn enabled_features() -> HashSet<String> {
+    FeatureSet::from_env()
+        .all_enabled()
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The optimization level of a cargo profile (`opt-level`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum OptLevel {
+    /// No optimizations (`0`).
+    O0,
+    /// Basic optimizations (`1`).
+    O1,
+    /// Some optimizations (`2`).
+    O2,
+    /// All optimizations (`3`).
+    O3,
+    /// Optimize for binary size (`s`).
+    S,
+    /// Optimize for binary size, but also turn off loop vectorization (`z`).
+    Z,
+}
+
+impl OptLevel {
+    /// Whether this level optimizes for binary size.
+    pub fn is_size(self) -> bool {
+        matches!(self, Self::S | Self::Z)
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "0" => Self::O0,
+            "1" => Self::O1,
+            "2" => Self::O2,
+            "3" => Self::O3,
+            "s" => Self::S,
+            "z" => Self::Z,
+            _ => bail!("Invalid opt-level '{s}'"),
+        })
+    }
+}
+
+impl Display for OptLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::O0 => "0",
+            Self::O1 => "1",
+            Self::O2 => "2",
+            Self::O3 => "3",
+            Self::S => "s",
+            Self::Z => "z",
+        })
+    }
+}
+
+/// The cargo profile the package whose build script is currently running is built with.
+///
+/// Cargo only tells build scripts whether the profile is `release` or inherits from
+/// `dev` (`PROFILE`), as well as its optimization level (`OPT_LEVEL`) and whether debug
+/// info is generated (`DEBUG`). Custom profiles are reported as the profile they inherit
+/// from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Profile {
+    /// The `dev` profile, or a custom profile inheriting from it.
+    Debug {
+        opt_level: OptLevel,
+        debug_info: bool,
+    },
+    /// The `release` profile, or a custom profile inheriting from it.
+    Release {
+        opt_level: OptLevel,
+        debug_info: bool,
+    },
+}
+
+impl Profile {
+    /// Get the profile from the `PROFILE`, `OPT_LEVEL` and `DEBUG` environment variables.
+    ///
+    /// Fails if any of them is not set (ie. when called outside of a build script).
+    pub fn current() -> Result<Self> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let get = |name: &str| {
+            var(name).ok_or_else(|| {
+                anyhow!("`{name}` env variable not set (maybe called outside of build script)")
+            })
+        };
+
+        let opt_level = get("OPT_LEVEL")?.parse()?;
+        let debug_info = !matches!(get("DEBUG")?.as_str(), "false" | "0" | "none");
+
+        Ok(match get("PROFILE")?.as_str() {
+            "release" => Self::Release {
+                opt_level,
+                debug_info,
+            },
+            _ => Self::Debug {
+                opt_level,
+                debug_info,
+            },
+        })
+    }
+
+    /// Whether this is the `release` profile or inherits from it.
+    pub fn is_release(&self) -> bool {
+        matches!(self, Self::Release { .. })
+    }
+
+    /// Whether this is the `dev` profile or inherits from it.
+    pub fn is_debug(&self) -> bool {
+        matches!(self, Self::Debug { .. })
+    }
+
+    /// The optimization level of this profile.
+    pub fn opt_level(&self) -> OptLevel {
+        match self {
+            Self::Debug { opt_level, .. } | Self::Release { opt_level, .. } => *opt_level,
+        }
+    }
+
+    /// Whether debug info is generated with this profile.
+    pub fn debug_info(&self) -> bool {
+        match self {
+            Self::Debug { debug_info, .. } | Self::Release { debug_info, .. } => *debug_info,
+        }
+    }
+}
+
 /// Extension trait for turning [`Display`]able values into cargo warnings.
 pub trait IntoWarning<R> {
     /// Print as a cargo warning.
@@ -450,3 +667,87 @@ pub fn workspace_dir() -> Option<PathBuf> {
     };
     Some(PathBuf::from(env::var_os("OUT_DIR")?).pop_times(pop_count))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature_set(vars: &[&str]) -> FeatureSet {
+        FeatureSet::from_vars(
+            vars.iter()
+                .map(|&var| (OsString::from(var), OsString::from("1"))),
+        )
+    }
+
+    #[test]
+    fn normalize_feature_names() {
+        assert_eq!(normalize_feature_name("foo"), "foo");
+        assert_eq!(normalize_feature_name("FOO"), "foo");
+        assert_eq!(normalize_feature_name("foo-bar"), "foo_bar");
+        assert_eq!(normalize_feature_name("foo_bar"), "foo_bar");
+        assert_eq!(normalize_feature_name("FOO_BAR"), "foo_bar");
+        assert_eq!(normalize_feature_name("Foo-Bar_baz"), "foo_bar_baz");
+    }
+
+    #[test]
+    fn feature_set_from_vars() {
+        let features = feature_set(&[
+            "CARGO_FEATURE_STD",
+            "CARGO_FEATURE_NATIVE_LIBS",
+            "CARGO_FEATURE_",
+            "CARGO_MANIFEST_DIR",
+            "OUT_DIR",
+        ]);
+
+        assert_eq!(
+            features.all_enabled().collect::<Vec<_>>(),
+            ["native_libs", "std"]
+        );
+
+        assert!(features.is_enabled("std"));
+        assert!(features.is_enabled("native-libs"));
+        assert!(features.is_enabled("native_libs"));
+        assert!(features.is_enabled("NATIVE_LIBS"));
+        assert!(!features.is_enabled("native"));
+        assert!(!features.is_enabled(""));
+
+        assert!(features.any_of(&["alloc", "native-libs"]));
+        assert!(!features.any_of(&["alloc", "nightly"]));
+        assert!(!features.any_of(&[]));
+    }
+
+    #[test]
+    fn profile_from_vars() {
+        let profile = |vars: &[(&str, &str)]| {
+            Profile::from_vars(|name| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            })
+        };
+
+        let release = profile(&[
+            ("PROFILE", "release"),
+            ("OPT_LEVEL", "z"),
+            ("DEBUG", "false"),
+        ])
+        .unwrap();
+        assert!(release.is_release());
+        assert_eq!(release.opt_level(), OptLevel::Z);
+        assert!(release.opt_level().is_size());
+        assert!(!release.debug_info());
+
+        let debug =
+            profile(&[("PROFILE", "debug"), ("OPT_LEVEL", "1"), ("DEBUG", "true")]).unwrap();
+        assert_eq!(
+            debug,
+            Profile::Debug {
+                opt_level: OptLevel::O1,
+                debug_info: true
+            }
+        );
+
+        assert!(profile(&[("PROFILE", "debug"), ("OPT_LEVEL", "4"), ("DEBUG", "true")]).is_err());
+        assert!(profile(&[("PROFILE", "debug")]).is_err());
+    }
+}
@@ -165,6 +165,77 @@ pub type NativeCommandArgs<'a> = WindowsCommandArgs<'a>;
 #[cfg(unix)]
 pub type NativeCommandArgs<'a> = UnixCommandArgs<'a>;
 
+/// Format `binary` and `args` as a command that can be copy-pasted into a shell of the
+/// current platform.
+///
+/// Every argument that is not made up only of characters without special meaning to the
+/// shell is quoted. On windows, arguments are wrapped in double quotes (see
+/// [`quote_windows_arg`]), everywhere else, in single quotes (see [`quote_posix_arg`]).
+pub fn format_shell_command(binary: &str, args: &[impl AsRef<str>]) -> String {
+    let quote = if cfg!(windows) {
+        quote_windows_arg
+    } else {
+        quote_posix_arg
+    };
+
+    std::iter::once(binary)
+        .chain(args.iter().map(AsRef::as_ref))
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote `arg` for a POSIX shell by wrapping it in single quotes, if required.
+///
+/// Single quotes in `arg` are replaced with `'\''`.
+pub fn quote_posix_arg(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Quote `arg` for the windows command line by wrapping it in double quotes, if required.
+///
+/// Besides whitespace and `"`, the characters `&`, `|`, `<`, `>` and `^` that are
+/// special to `cmd.exe` are quoted as well. The result is parsed back to `arg` by
+/// [`WindowsCommandArgs`].
+pub fn quote_windows_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"', '&', '|', '<', '>', '^']) {
+        return arg.to_owned();
+    }
+
+    let mut result = String::with_capacity(arg.len() + 2);
+    result.push('"');
+
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote must be escaped as well as the quote itself.
+                result.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                result.extend(std::iter::repeat('\\').take(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            result.push(c);
+        }
+    }
+    // Backslashes before the closing quote must be escaped.
+    result.extend(std::iter::repeat('\\').take(backslashes * 2));
+    result.push('"');
+
+    result
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -191,4 +262,64 @@ mod test {
         assert_eq!(iter.next(), Some("rest a b   "));
         assert_eq!(iter.next(), None);
     }
+
+    #[test]
+    fn quote_posix_args() {
+        assert_eq!(quote_posix_arg("-Wl,--gc-sections"), "-Wl,--gc-sections");
+        assert_eq!(quote_posix_arg(""), "''");
+        assert_eq!(quote_posix_arg("a b"), "'a b'");
+        assert_eq!(quote_posix_arg("it's"), r"'it'\''s'");
+        assert_eq!(quote_posix_arg("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn quote_windows_args() {
+        let args = [
+            r"C:\path\to\gcc.exe",
+            "",
+            "with space",
+            r#"quote"d"#,
+            r#"back\"slash"#,
+            r"trailing dir\",
+            "a&b",
+            "a|b",
+            "<in",
+            ">out",
+            "^caret",
+        ];
+
+        let quoted = args
+            .iter()
+            .map(|a| quote_windows_arg(a))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            quoted,
+            [
+                r"C:\path\to\gcc.exe",
+                r#""""#,
+                r#""with space""#,
+                r#""quote\"d""#,
+                r#""back\\\"slash""#,
+                r#""trailing dir\\""#,
+                r#""a&b""#,
+                r#""a|b""#,
+                r#""<in""#,
+                r#"">out""#,
+                r#""^caret""#,
+            ]
+        );
+        assert_eq!(
+            WindowsCommandArgs::new(&quoted.join(" ")).collect::<Vec<_>>(),
+            args
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn format_posix_shell_command() {
+        assert_eq!(
+            format_shell_command("riscv32-esp-elf-gcc", &["-o", "out dir/app.elf", "-lc"]),
+            "riscv32-esp-elf-gcc -o 'out dir/app.elf' -lc"
+        );
+    }
 }
@@ -15,9 +15,15 @@ use crate::build::{CInclArgs, LinkArgsBuilder};
 use crate::cli::NativeCommandArgs;
 use crate::cmd;
 
+pub mod cache;
 pub mod file_api;
+pub mod idf_sdk;
+pub mod session;
+pub use cache::CmakeCache;
 pub use dep_cmake::*;
 pub use file_api::Query;
+pub use idf_sdk::IdfSdkPackage;
+pub use session::CmakeSession;
 
 /// An enum for parsing and passing to cmake the standard command-line generators.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, EnumString, Display, EnumIter, IntoStaticStr)]
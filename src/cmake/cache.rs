@@ -0,0 +1,333 @@
+//! Reading and writing of the persistent cmake cache file (`CMakeCache.txt`).
+
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Error, Result};
+
+pub use super::file_api::cache::Type;
+
+/// The banner line that encloses the section titles of a cache file.
+const SECTION_BANNER: &str = "########################";
+const EXTERNAL_SECTION_TITLE: &str = "# EXTERNAL cache entries";
+const INTERNAL_SECTION_TITLE: &str = "# INTERNAL cache entries";
+
+/// A cmake cache file (`CMakeCache.txt`).
+///
+/// [`Display`]ing a cache writes it in the same layout cmake uses, so that a cache
+/// written by cmake and parsed by [`CmakeCache::from_file`] is written back unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CmakeCache {
+    /// All `#` comment lines (including the `#`) at the start of the file, except the
+    /// section titles (e.g. `# This is the CMakeCache file.`).
+    pub header: Vec<String>,
+    /// All cache entries in the order they appear in the file.
+    pub entries: Vec<CacheEntry>,
+}
+
+/// A single `KEY:TYPE=VALUE` entry of a [`CmakeCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// The name of the entry.
+    pub name: String,
+    /// The type of the entry.
+    pub entry_type: Type,
+    /// The value of the entry.
+    pub value: String,
+    /// All lines of the help string without the leading `//`.
+    pub help: Vec<String>,
+}
+
+impl CacheEntry {
+    /// Create a new entry without a help string.
+    pub fn new(name: impl Into<String>, entry_type: Type, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            entry_type,
+            value: value.into(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Parse a `KEY:TYPE=VALUE` or `KEY=VALUE` line.
+    fn parse(line: &str, help: Vec<String>) -> Option<Self> {
+        let (name, rest) = if let Some(rest) = line.strip_prefix('"') {
+            rest.split_once('"')?
+        } else {
+            line.split_at(line.find([':', '='])?)
+        };
+
+        let (entry_type, value) = if let Some(rest) = rest.strip_prefix(':') {
+            let (entry_type, value) = rest.split_once('=')?;
+            (Type::from(entry_type.to_owned()), value)
+        } else {
+            (Type::Uninitialized, rest.strip_prefix('=')?)
+        };
+
+        let value = value.trim_end_matches([' ', '\t', '\r']);
+        let value = if value.len() >= 2 {
+            value
+                .strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .unwrap_or(value)
+        } else {
+            value
+        };
+
+        Some(Self {
+            name: name.to_owned(),
+            entry_type,
+            value: value.to_owned(),
+            help,
+        })
+    }
+}
+
+impl Display for CacheEntry {
+    /// Write the help string and the entry line the same way cmake does.
+    ///
+    /// Names containing a `:` or starting with `//` are double-quoted, values ending with
+    /// whitespace are single-quoted and values are truncated at the first newline.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.help.is_empty() && self.entry_type != Type::Internal {
+            writeln!(f, "//Missing description")?;
+        }
+        for line in &self.help {
+            writeln!(f, "//{line}")?;
+        }
+
+        if self.name.contains(':') || self.name.starts_with("//") {
+            write!(f, "\"{}\"", self.name)?;
+        } else {
+            f.write_str(&self.name)?;
+        }
+
+        let value = self.value.split('\n').next().unwrap_or_default();
+        if value.ends_with([' ', '\t']) {
+            writeln!(f, ":{}='{value}'", self.entry_type)
+        } else {
+            writeln!(f, ":{}={value}", self.entry_type)
+        }
+    }
+}
+
+impl CmakeCache {
+    /// Read and parse the cache file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|s| s.parse())
+            .with_context(|| anyhow!("Parsing cmake cache file '{}' failed", path.display()))
+    }
+
+    /// Write this cache to the file at `path` (see [`Display`]).
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        crate::fs::atomic_write(path, self.to_string())
+            .with_context(|| anyhow!("Could not write cmake cache file '{}'", path.display()))
+    }
+
+    /// Get the entry named `name`.
+    pub fn get(&self, name: &str) -> Option<&CacheEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Get the mutable entry named `name`.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut CacheEntry> {
+        self.entries.iter_mut().find(|e| e.name == name)
+    }
+
+    /// Set the type and value of the entry named `name`, adding a new entry if it
+    /// doesn't exist.
+    pub fn set(&mut self, name: impl Into<String>, entry_type: Type, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+
+        if let Some(entry) = self.get_mut(&name) {
+            entry.entry_type = entry_type;
+            entry.value = value;
+        } else {
+            self.entries.push(CacheEntry::new(name, entry_type, value));
+        }
+    }
+
+    /// Remove the entry named `name` and return it.
+    pub fn remove(&mut self, name: &str) -> Option<CacheEntry> {
+        let index = self.entries.iter().position(|e| e.name == name)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+impl FromStr for CmakeCache {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut cache = CmakeCache::default();
+        let mut help = Vec::new();
+
+        for (line_no, line) in s.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() {
+                continue;
+            } else if let Some(help_line) = line.strip_prefix("//") {
+                help.push(help_line.trim_end_matches('\r').to_owned());
+            } else if line.starts_with('#') {
+                let line = line.trim_end();
+                if cache.entries.is_empty()
+                    && ![
+                        SECTION_BANNER,
+                        EXTERNAL_SECTION_TITLE,
+                        INTERNAL_SECTION_TITLE,
+                    ]
+                    .contains(&line)
+                {
+                    cache.header.push(line.to_owned());
+                }
+            } else {
+                let entry = CacheEntry::parse(line, std::mem::take(&mut help))
+                    .ok_or_else(|| anyhow!("invalid cache entry on line {}", line_no + 1))?;
+                cache.entries.push(entry);
+            }
+        }
+
+        Ok(cache)
+    }
+}
+
+impl Display for CmakeCache {
+    /// Write the cache in the `CMakeCache.txt` format.
+    ///
+    /// All entries that are not of type [`Type::Internal`] are written to the external
+    /// section, the rest to the internal section, each in the order of
+    /// [`entries`](CmakeCache::entries).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{line}")?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "{SECTION_BANNER}\n{EXTERNAL_SECTION_TITLE}\n{SECTION_BANNER}\n"
+        )?;
+        for entry in self
+            .entries
+            .iter()
+            .filter(|e| e.entry_type != Type::Internal)
+        {
+            writeln!(f, "{entry}")?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "{SECTION_BANNER}\n{INTERNAL_SECTION_TITLE}\n{SECTION_BANNER}\n"
+        )?;
+        for entry in self
+            .entries
+            .iter()
+            .filter(|e| e.entry_type == Type::Internal)
+        {
+            write!(f, "{entry}")?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CACHE: &str = r#"# This is the CMakeCache file.
+# For build in directory: /home/user/project/build
+# It was generated by CMake: /usr/bin/cmake
+# You can edit this file to change values found and used by cmake.
+# If you do not want to change any of the values, simply exit the editor.
+# If you do want to change a value, simply edit, save, and exit the editor.
+# The syntax for the file is as follows:
+# KEY:TYPE=VALUE
+# KEY is the name of a variable in the cache.
+# TYPE is a hint to GUIs for the type of VALUE, DO NOT EDIT TYPE!.
+# VALUE is the current value for the KEY.
+
+########################
+# EXTERNAL cache entries
+########################
+
+//Choose the type of build, options are: None Debug Release RelWithDebInfo
+// MinSizeRel ...
+CMAKE_BUILD_TYPE:STRING=Debug
+
+//C compiler
+CMAKE_C_COMPILER:FILEPATH=/usr/bin/cc
+
+//Enable/Disable color output during build.
+CMAKE_COLOR_MAKEFILE:BOOL=ON
+
+//Value with a trailing space
+"WITH:COLON":STRING='trailing '
+
+
+########################
+# INTERNAL cache entries
+########################
+
+//ADVANCED property for variable: CMAKE_C_COMPILER
+CMAKE_C_COMPILER-ADVANCED:INTERNAL=1
+//Major version of cmake used to create the current loaded cache
+CMAKE_CACHE_MAJOR_VERSION:INTERNAL=3
+CMAKE_NO_HELP:INTERNAL=
+
+"#;
+
+    #[test]
+    fn cmake_cache_round_trip() {
+        let cache = CACHE.parse::<CmakeCache>().unwrap();
+
+        assert_eq!(cache.header.len(), 11);
+        assert_eq!(cache.entries.len(), 7);
+        assert_eq!(
+            cache.get("CMAKE_BUILD_TYPE"),
+            Some(&CacheEntry {
+                name: "CMAKE_BUILD_TYPE".into(),
+                entry_type: Type::String,
+                value: "Debug".into(),
+                help: vec![
+                    "Choose the type of build, options are: None Debug Release RelWithDebInfo"
+                        .into(),
+                    " MinSizeRel ...".into()
+                ],
+            })
+        );
+        assert_eq!(cache.get("WITH:COLON").unwrap().value, "trailing ");
+        assert_eq!(
+            cache.get("CMAKE_CACHE_MAJOR_VERSION").unwrap().entry_type,
+            Type::Internal
+        );
+
+        assert_eq!(cache.to_string(), CACHE);
+    }
+
+    #[test]
+    fn cmake_cache_set() {
+        let mut cache = CACHE.parse::<CmakeCache>().unwrap();
+        cache.set("CMAKE_BUILD_TYPE", Type::String, "Release");
+        cache.set("NEW_VAR", Type::Bool, "OFF");
+
+        let cache = cache.to_string().parse::<CmakeCache>().unwrap();
+        assert_eq!(cache.get("CMAKE_BUILD_TYPE").unwrap().value, "Release");
+        assert_eq!(cache.get("CMAKE_BUILD_TYPE").unwrap().help.len(), 2);
+        assert_eq!(
+            cache.get("NEW_VAR"),
+            Some(&CacheEntry {
+                name: "NEW_VAR".into(),
+                entry_type: Type::Bool,
+                value: "OFF".into(),
+                help: vec!["Missing description".into()],
+            })
+        );
+    }
+}
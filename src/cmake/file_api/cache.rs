@@ -77,6 +77,21 @@ impl From<String> for Type {
     }
 }
 
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Bool => "BOOL",
+            Self::Path => "PATH",
+            Self::Filepath => "FILEPATH",
+            Self::String => "STRING",
+            Self::Internal => "INTERNAL",
+            Self::Static => "STATIC",
+            Self::Uninitialized => "UNINITIALIZED",
+            Self::Other(s) => s,
+        })
+    }
+}
+
 /// A property set for an [`Entry`].
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "UPPERCASE", tag = "name", content = "value")]
@@ -197,6 +197,13 @@ pub mod target {
         /// The type of the target.
         #[serde(rename = "type")]
         pub target_type: Type,
+        /// The file name of the target's main artifact.
+        ///
+        /// Present for executable and non-interface library targets.
+        pub name_on_disk: Option<String>,
+        /// All artifacts produced by the target.
+        #[serde(default)]
+        pub artifacts: Vec<Artifact>,
     }
 
     impl Target {
@@ -214,6 +221,14 @@ pub mod target {
         }
     }
 
+    /// An artifact produced by a target.
+    #[derive(Debug, Deserialize, Clone)]
+    pub struct Artifact {
+        /// The path to the artifact, relative to the top-level build directory if not
+        /// absolute.
+        pub path: PathBuf,
+    }
+
     /// Compile settings for groups of sources using the same settings.
     #[derive(Debug, Deserialize, Clone)]
     #[serde(rename_all = "camelCase")]
@@ -222,8 +222,8 @@ impl Replies {
         let replies: HashMap<ObjKind, Reply> =
             serde_json::from_value::<HashMap<String, ReplyOrError>>(reply)
                 .with_context(base_error)?
-                .into_iter()
-                .filter_map(|(_, v)| match v {
+                .into_values()
+                .filter_map(|v| match v {
                     ReplyOrError::Reply(mut r) => {
                         if let Err(err) = r.kind.check_version_supported(r.version.major) {
                             errors.push(err.to_string());
@@ -0,0 +1,256 @@
+//! Locating the esp-idf used as the SDK of a cmake project.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+/// The environment variable containing the path to the esp-idf.
+const IDF_PATH_VAR: &str = "IDF_PATH";
+
+/// An esp-idf source tree that cmake projects can use as their SDK.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IdfSdkPackage {
+    idf_path: PathBuf,
+}
+
+impl IdfSdkPackage {
+    /// Find the esp-idf of the crate whose build script is running.
+    ///
+    /// The following locations are tried in order:
+    /// 1. The `IDF_PATH` environment variable.
+    /// 2. (feature `manifest`) The `--ldproxy-linker` argument in the `rustflags` of
+    ///    the `[target.<target triple>]` section of the first `.cargo/config.toml` (or
+    ///    `.cargo/config`) found in the `OUT_DIR` or any of its parent directories. The
+    ///    esp-idf is assumed to be installed next to the `tools` directory that contains
+    ///    the linker.
+    pub fn find() -> Result<Self> {
+        if let Some(idf_path) = env::var_os(IDF_PATH_VAR).filter(|p| !p.is_empty()) {
+            return Self::from_path(idf_path);
+        }
+
+        #[cfg(feature = "manifest")]
+        if let Some(idf_path) = Self::find_from_cargo_config()? {
+            return Self::from_path(idf_path);
+        }
+
+        bail!("Could not find the esp-idf, `{IDF_PATH_VAR}` is not set")
+    }
+
+    /// Use the esp-idf at `idf_path`.
+    pub fn from_path(idf_path: impl Into<PathBuf>) -> Result<Self> {
+        let idf_path = idf_path.into();
+        if !is_esp_idf(&idf_path) {
+            bail!(
+                "'{}' is not an esp-idf source tree (missing 'tools/cmake/project.cmake')",
+                idf_path.display()
+            );
+        }
+
+        Ok(Self { idf_path })
+    }
+
+    /// The root directory of the esp-idf.
+    pub fn idf_path(&self) -> &Path {
+        &self.idf_path
+    }
+
+    /// The cmake script that must be included by esp-idf cmake projects.
+    pub fn project_cmake(&self) -> PathBuf {
+        self.idf_path
+            .join("tools")
+            .join("cmake")
+            .join("project.cmake")
+    }
+
+    /// The cmake toolchain file for `chip` (ex. `esp32c3`).
+    pub fn toolchain_file(&self, chip: &str) -> PathBuf {
+        self.idf_path
+            .join("tools")
+            .join("cmake")
+            .join(format!("toolchain-{chip}.cmake"))
+    }
+
+    #[cfg(feature = "manifest")]
+    fn find_from_cargo_config() -> Result<Option<PathBuf>> {
+        let (out_dir, target) = match (env::var_os("OUT_DIR"), env::var("TARGET")) {
+            (Some(out_dir), Ok(target)) => (PathBuf::from(out_dir), target),
+            _ => return Ok(None),
+        };
+
+        let linker = crate::cargo::Crate::new(out_dir).scan_config_toml(|config| {
+            let rustflags = config.get("target")?.get(&target)?.get("rustflags")?;
+            let rustflags = match rustflags {
+                toml::Value::String(flags) => flags.split_whitespace().map(str::to_owned).collect(),
+                toml::Value::Array(flags) => flags
+                    .iter()
+                    .filter_map(|flag| flag.as_str().map(str::to_owned))
+                    .collect(),
+                _ => return None,
+            };
+
+            ldproxy_linker_from_rustflags(rustflags)
+        })?;
+
+        Ok(linker.and_then(|linker| {
+            let idf_path = idf_path_from_linker(Path::new(&linker));
+            match &idf_path {
+                Some(idf_path) => log::debug!(
+                    "Using esp-idf '{}' derived from the ldproxy linker '{linker}'",
+                    idf_path.display()
+                ),
+                None => {
+                    log::debug!("Could not derive the esp-idf from the ldproxy linker '{linker}'")
+                }
+            }
+            idf_path
+        }))
+    }
+}
+
+/// Whether `dir` is the root of an esp-idf source tree.
+fn is_esp_idf(dir: &Path) -> bool {
+    dir.join("tools")
+        .join("cmake")
+        .join("project.cmake")
+        .is_file()
+}
+
+/// Get the value of the last `--ldproxy-linker` argument passed to the linker by
+/// `rustflags`.
+///
+/// Arguments are taken from `-C link-arg=..`, `-Clink-arg=..` and `-C link-args=..`
+/// rustflags, as well as from bare `--ldproxy-linker` rustflags.
+#[cfg(feature = "manifest")]
+fn ldproxy_linker_from_rustflags(rustflags: Vec<String>) -> Option<String> {
+    use crate::cli::ParseFrom;
+
+    let mut link_args = Vec::new();
+    let mut rustflags = rustflags.into_iter();
+    while let Some(flag) = rustflags.next() {
+        let codegen_opt = if flag == "-C" {
+            match rustflags.next() {
+                Some(opt) => opt,
+                None => break,
+            }
+        } else if let Some(opt) = flag.strip_prefix("-C") {
+            opt.to_owned()
+        } else {
+            link_args.push(flag);
+            continue;
+        };
+
+        if let Some(arg) = codegen_opt.strip_prefix("link-arg=") {
+            link_args.push(arg.to_owned());
+        } else if let Some(args) = codegen_opt.strip_prefix("link-args=") {
+            link_args.extend(args.split_whitespace().map(str::to_owned));
+        }
+    }
+
+    crate::build::LDPROXY_LINKER_ARG
+        .parse_from(&mut link_args)
+        .ok()?
+        .pop()
+}
+
+/// Derive the esp-idf from the path of a `linker` installed by the esp-idf tools
+/// installer.
+///
+/// The toolchains are installed into `<install dir>/tools/<tool>/<version>/..`, and the
+/// esp-idf is expected at `<install dir>/esp-idf` or, for esp-idf versions managed by
+/// embuild, at the single `<install dir>/esp-idf/<version>` directory.
+#[cfg(feature = "manifest")]
+fn idf_path_from_linker(linker: &Path) -> Option<PathBuf> {
+    let install_dir = linker
+        .ancestors()
+        .find(|dir| dir.file_name().map(|name| name == "tools").unwrap_or(false))?
+        .parent()?;
+    let esp_idf_dir = install_dir.join("esp-idf");
+
+    if is_esp_idf(&esp_idf_dir) {
+        return Some(esp_idf_dir);
+    }
+
+    let mut candidates = esp_idf_dir
+        .read_dir()
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|dir| is_esp_idf(dir))
+        .collect::<Vec<_>>();
+
+    match candidates.len() {
+        1 => candidates.pop(),
+        0 => None,
+        _ => {
+            log::warn!(
+                "Found multiple esp-idf versions in '{}', set `{IDF_PATH_VAR}` to select one",
+                esp_idf_dir.display()
+            );
+            None
+        }
+    }
+}
+
+#[cfg(all(test, feature = "manifest"))]
+mod tests {
+    use super::*;
+
+    fn create_esp_idf(dir: &Path) {
+        std::fs::create_dir_all(dir.join("tools/cmake")).unwrap();
+        std::fs::write(dir.join("tools/cmake/project.cmake"), "").unwrap();
+    }
+
+    #[test]
+    fn idf_path_from_ldproxy_linker() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let linker = install_dir.path().join(
+            "tools/riscv32-esp-elf/esp-13.2.0_20230928/riscv32-esp-elf/bin/riscv32-esp-elf-gcc",
+        );
+
+        assert_eq!(idf_path_from_linker(&linker), None);
+
+        create_esp_idf(&install_dir.path().join("esp-idf/v5.1.2"));
+        assert_eq!(
+            idf_path_from_linker(&linker),
+            Some(install_dir.path().join("esp-idf/v5.1.2"))
+        );
+
+        create_esp_idf(&install_dir.path().join("esp-idf/v5.2.1"));
+        assert_eq!(idf_path_from_linker(&linker), None);
+
+        create_esp_idf(&install_dir.path().join("esp-idf"));
+        assert_eq!(
+            idf_path_from_linker(&linker),
+            Some(install_dir.path().join("esp-idf"))
+        );
+    }
+
+    #[test]
+    fn ldproxy_linker_rustflags() {
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect();
+
+        assert_eq!(
+            ldproxy_linker_from_rustflags(flags(&[
+                "--cfg",
+                "espidf_time64",
+                "--ldproxy-linker=/a/gcc",
+                "--ldproxy-cwd=/a"
+            ])),
+            Some("/a/gcc".into())
+        );
+        assert_eq!(
+            ldproxy_linker_from_rustflags(flags(&[
+                "-C",
+                "link-arg=--ldproxy-linker",
+                "-C",
+                "link-arg=/a/gcc",
+                "-Clink-arg=--ldproxy-linker=/b/gcc"
+            ])),
+            Some("/b/gcc".into())
+        );
+        assert_eq!(
+            ldproxy_linker_from_rustflags(flags(&["-C", "link-args=-Wl,--gc-sections"])),
+            None
+        );
+    }
+}
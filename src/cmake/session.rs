@@ -0,0 +1,122 @@
+//! Running the build and install steps of a configured cmake build directory.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::cmake;
+use crate::cargo::track_file;
+use crate::cmd;
+
+/// A configured cmake build directory on which the steps following the configure step
+/// can be run.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CmakeSession {
+    build_dir: PathBuf,
+}
+
+impl CmakeSession {
+    /// Create a session for the already configured cmake build directory `build_dir`.
+    pub fn new(build_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            build_dir: build_dir.into(),
+        }
+    }
+
+    /// The cmake build directory.
+    pub fn build_dir(&self) -> &Path {
+        &self.build_dir
+    }
+
+    /// Build the default target using `cmake --build <build_dir>`.
+    pub fn build(&self) -> Result<()> {
+        self.run(
+            "build",
+            vec![OsStr::new("--build"), self.build_dir.as_os_str()],
+        )
+    }
+
+    /// Install the project to `prefix` using `cmake --install <build_dir> --prefix
+    /// <prefix>`.
+    ///
+    /// The build script is rerun if the generated `cmake_install.cmake` changes.
+    pub fn install(&self, prefix: &Path) -> Result<()> {
+        self.run_install(prefix, None)
+    }
+
+    /// Install only `component` of the project to `prefix` using `cmake --install
+    /// <build_dir> --prefix <prefix> --component <component>`.
+    ///
+    /// The build script is rerun if the generated `cmake_install.cmake` changes.
+    pub fn install_component(&self, component: &str, prefix: &Path) -> Result<()> {
+        self.run_install(prefix, Some(component))
+    }
+
+    fn run_install(&self, prefix: &Path, component: Option<&str>) -> Result<()> {
+        self.run("install", self.install_args(prefix, component))?;
+        track_file(self.build_dir.join("cmake_install.cmake"));
+
+        Ok(())
+    }
+
+    /// The arguments of `cmake --install`.
+    fn install_args<'a>(&'a self, prefix: &'a Path, component: Option<&'a str>) -> Vec<&'a OsStr> {
+        let mut args = vec![
+            OsStr::new("--install"),
+            self.build_dir.as_os_str(),
+            OsStr::new("--prefix"),
+            prefix.as_os_str(),
+        ];
+        if let Some(component) = component {
+            args.extend([OsStr::new("--component"), OsStr::new(component)]);
+        }
+
+        args
+    }
+
+    /// Run cmake with `args` and log its output.
+    ///
+    /// If cmake fails, the returned error contains its stderr output.
+    fn run(&self, step: &str, args: Vec<&OsStr>) -> Result<()> {
+        let output = cmd!(cmake(); args=(args)).stdout().with_context(|| {
+            anyhow!(
+                "cmake {step} failed in build directory '{}'",
+                self.build_dir.display()
+            )
+        })?;
+
+        for line in output.lines() {
+            log::debug!("cmake {step}: {line}");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_args() {
+        let session = CmakeSession::new("build");
+        let prefix = Path::new("out/install");
+
+        assert_eq!(
+            session.install_args(prefix, None),
+            ["--install", "build", "--prefix", "out/install"]
+        );
+        assert_eq!(
+            session.install_args(prefix, Some("headers")),
+            [
+                "--install",
+                "build",
+                "--prefix",
+                "out/install",
+                "--component",
+                "headers"
+            ]
+        );
+    }
+}
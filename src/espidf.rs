@@ -12,16 +12,18 @@
 use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
 use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{env, fs};
 
-use anyhow::{anyhow, Context, Error, Result};
+use anyhow::{anyhow, bail, Context, Error, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::python::PYTHON;
-use crate::{cmd, git, path_buf, python};
+use crate::{cargo, cmd, git, path_buf, python};
 
 use self::tools_schema::{
     PlatformDownloadInfo, PlatformOverrideInfoPlatformsItem, ToolInfo, VersionInfo,
@@ -30,6 +32,8 @@ use self::tools_schema::{
 #[cfg(feature = "elf")]
 pub mod ulp_fsm;
 
+pub mod openocd;
+
 mod tools_schema;
 
 pub const DEFAULT_ESP_IDF_REPOSITORY: &str = "https://github.com/espressif/esp-idf.git";
@@ -42,12 +46,23 @@ pub const IDF_TOOLS_PATH_VAR: &str = "IDF_TOOLS_PATH";
 
 const IDF_PYTHON_ENV_PATH_VAR: &str = "IDF_PYTHON_ENV_PATH";
 
+/// Environment variable containing a mirror host (and optional path) that replaces
+/// `https://github.com/` in all download urls (same as for `idf_tools.py`).
+pub const IDF_GITHUB_ASSETS_VAR: &str = "IDF_GITHUB_ASSETS";
+/// Environment variable containing a `;`-separated list of `<pattern>,<replacement>`
+/// download url rewrite rules (same as for `idf_tools.py`).
+pub const IDF_MIRROR_PREFIX_MAP_VAR: &str = "IDF_MIRROR_PREFIX_MAP";
+
 /// The global install dir of the esp-idf and its tools, relative to the user home dir.
 pub const GLOBAL_INSTALL_DIR: &str = ".espressif";
 
 /// Default filename for the file that contains [`EspIdfBuildInfo`].
 pub const BUILD_INFO_FILENAME: &str = "esp-idf-build.json";
 
+/// The name of the marker file written into the directory of a tool after it was
+/// installed completely.
+const TOOL_INSTALLED_MARKER: &str = ".embuild-installed";
+
 /// One or more esp-idf tools.
 #[derive(Debug, Clone)]
 pub struct Tools {
@@ -152,9 +167,14 @@ impl Tool {
         if let Some(mut test_command) = self.test_command() {
             log::debug!("Run cmd: {test_command:?} to get current tool version");
 
-            let output = test_command.output().unwrap_or_else(|e| {
-                panic!("Failed to run command: {test_command:?}; error: {e:?}")
-            });
+            // The command can't be run if the tool was only partially extracted.
+            let output = match test_command.output() {
+                Ok(output) => output,
+                Err(e) => {
+                    log::debug!("Failed to run command: {test_command:?}; error: {e:?}");
+                    return false;
+                }
+            };
 
             if !self.version_regex.is_empty() {
                 let regex =
@@ -176,6 +196,103 @@ impl Tool {
         }
     }
 
+    /// The directory this version of the tool is installed in.
+    fn version_dir(&self) -> PathBuf {
+        path_buf![&self.install_dir, "tools", &self.name, &self.version]
+    }
+
+    /// The marker file written after the tool was installed successfully.
+    fn installed_marker(&self) -> PathBuf {
+        self.version_dir().join(TOOL_INSTALLED_MARKER)
+    }
+
+    /// Check that the installation of this tool completed.
+    ///
+    /// That is the case if it has a [`Self::installed_marker`] and its version dir
+    /// contains the expected top-level entry of the tool's archive.
+    fn is_complete(&self) -> bool {
+        self.installed_marker().exists() && self.has_expected_content()
+    }
+
+    /// Check that the version dir contains the expected top-level entry of the tool's
+    /// archive.
+    fn has_expected_content(&self) -> bool {
+        let version_dir = self.version_dir();
+        match self
+            .abs_export_path()
+            .strip_prefix(&version_dir)
+            .ok()
+            .and_then(|p| p.components().next())
+        {
+            Some(top_level) => version_dir.join(top_level).exists(),
+            // The archive is extracted directly into the version dir, so at least one
+            // entry besides the marker must exist.
+            None => fs::read_dir(&version_dir)
+                .map(|mut entries| {
+                    entries.any(|e| {
+                        e.map(|e| e.file_name() != TOOL_INSTALLED_MARKER)
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false),
+        }
+    }
+
+    /// Remove an incomplete installation of this tool, so that it will be installed
+    /// again.
+    ///
+    /// An installation without [`Self::installed_marker`] is removed, as the marker is
+    /// only missing if the installation was interrupted, unless the install dir is
+    /// `shared` with other installers (like the global install dir). There the tool may
+    /// have been installed by `idf_tools.py` itself, so it is never removed but only
+    /// marked as installed if it has the expected content and passes [`Self::test`];
+    /// otherwise it is left to `idf_tools.py` to reinstall it.
+    ///
+    /// Returns `true` if an incomplete installation was removed.
+    fn remove_if_incomplete(&self, shared: bool) -> Result<bool> {
+        let version_dir = self.version_dir();
+        if !version_dir.exists() || self.is_complete() {
+            return Ok(false);
+        }
+
+        if shared && !self.installed_marker().exists() {
+            if self.has_expected_content() && self.test() {
+                self.write_installed_marker()?;
+            } else {
+                log::debug!(
+                    "Tool '{}' ({}) in '{}' is not installed correctly, but may not have been \
+                     installed by embuild, so it is not removed",
+                    self.name,
+                    self.version,
+                    version_dir.display()
+                );
+            }
+            return Ok(false);
+        }
+
+        log::warn!(
+            "Tool '{}' ({}) is only partially installed, removing '{}' to reinstall it",
+            self.name,
+            self.version,
+            version_dir.display()
+        );
+        remove_dir_all::remove_dir_all(&version_dir).with_context(|| {
+            anyhow!(
+                "Failed to remove partially installed tool '{}'",
+                version_dir.display()
+            )
+        })?;
+
+        Ok(true)
+    }
+
+    /// Mark this tool as completely installed.
+    fn write_installed_marker(&self) -> Result<()> {
+        let marker = self.installed_marker();
+        crate::fs::atomic_write(&marker, format!("{} {}\n", self.name, self.version))
+            .with_context(|| anyhow!("Failed to write install marker '{}'", marker.display()))
+    }
+
     /// get the absolute PATH
     fn abs_export_path(&self) -> PathBuf {
         self.install_dir.join(self.export_path.as_path())
@@ -243,28 +360,8 @@ fn parse_tools(
             version.status == Some(tools_schema::VersionInfoStatus::Recommended)
         }).for_each(|version| {
 
-            let os_matcher = |info: &VersionInfo| -> Option<PlatformDownloadInfo> {
-                let os = std::env::consts::OS;
-                let arch = std::env::consts::ARCH;
-                // The ARCH const in Rust does not differentiate between armel
-                // and armhf. Assume armel for maximum compatibility.
-                match (os, arch) {
-                    ("linux", "x86") => info.linux_i686.clone(),
-                    ("linux", "x86_64") => info.linux_amd64.clone(),
-                    ("linux", "arm") => info.linux_armel.clone(),
-                    ("linux", "aarch64") => info.linux_arm64.clone(),
-                    ("macos", "x86_64") => info.macos.clone(),
-                    ("macos", "aarch64") => info.macos_arm64.clone(),
-                    ("windows", "x86") => info.win32.clone(),
-                    ("windows", "x86_64") => info.win64.clone(),
-                    _ => None,
-                }
-            };
-
             // either a any key is provided or only platform specific keys
-            let info = if let Some(plaform_dll_info) = version.any.clone() {
-                plaform_dll_info
-            } else if let Some(plaform_dll_info) = os_matcher(version) {
+            let info = if let Some(plaform_dll_info) = host_download_info(version) {
                 plaform_dll_info
             } else {
                 panic!("Neither any or platform specifc match found. Please create an issue on https://github.com/esp-rs/embuild and report your operating system");
@@ -340,6 +437,171 @@ fn parse_tools(
     Ok(tools)
 }
 
+/// Get the download of the tool version `info` for the host platform.
+fn host_download_info(info: &VersionInfo) -> Option<PlatformDownloadInfo> {
+    if let Some(any) = &info.any {
+        return Some(any.clone());
+    }
+
+    let os = std::env::consts::OS;
+    let arch = std::env::consts::ARCH;
+    // The ARCH const in Rust does not differentiate between armel
+    // and armhf. Assume armel for maximum compatibility.
+    match (os, arch) {
+        ("linux", "x86") => info.linux_i686.clone(),
+        ("linux", "x86_64") => info.linux_amd64.clone(),
+        ("linux", "arm") => info.linux_armel.clone(),
+        ("linux", "aarch64") => info.linux_arm64.clone(),
+        ("macos", "x86_64") => info.macos.clone(),
+        ("macos", "aarch64") => info.macos_arm64.clone(),
+        ("windows", "x86") => info.win32.clone(),
+        ("windows", "x86_64") => info.win64.clone(),
+        _ => None,
+    }
+}
+
+/// The gcc toolchain an esp-idf release uses for a chip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolchainSpec {
+    /// The name of the tool in `tools.json` (ex. `xtensa-esp-elf`).
+    pub tool_name: String,
+    /// The recommended version of the tool.
+    pub version: String,
+    /// The name of the compiler executable (ex. `xtensa-esp32-elf-gcc`).
+    pub compiler: String,
+    /// The download of the tool for the host platform.
+    pub download: ToolchainDownload,
+}
+
+/// The archive containing a [`ToolchainSpec`] tool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ToolchainDownload {
+    /// The download url of the archive.
+    pub url: String,
+    /// The sha256 hash of the archive.
+    pub sha256: String,
+    /// The size of the archive in bytes.
+    pub size: u64,
+}
+
+/// Get the gcc toolchain the esp-idf checkout at `esp_idf_dir` uses for `chip` (ex.
+/// `esp32c3`), as listed in its `tools/tools.json`.
+///
+/// This allows installing the exact compiler of an esp-idf version without running its
+/// `idf_tools.py` installer.
+pub fn toolchain_for(esp_idf_dir: &Path, chip: &str) -> Result<ToolchainSpec> {
+    let tools_json = path_buf![esp_idf_dir, "tools", "tools.json"];
+    let version = EspIdfVersion::format(&EspIdfVersion::try_from(esp_idf_dir));
+
+    let tools_info = fs::read_to_string(&tools_json)
+        .map_err(Error::from)
+        .and_then(|s| Ok(serde_json::from_str::<ToolsInfo>(&s)?))
+        .with_context(|| anyhow!("Failed to read '{}'", tools_json.display()))?;
+
+    find_toolchain(&tools_info, chip).with_context(|| {
+        anyhow!(
+            "Failed to find the toolchain for '{chip}' of esp-idf {version} ('{}')",
+            esp_idf_dir.display()
+        )
+    })
+}
+
+/// The chips with an xtensa cpu, all other chips are riscv.
+const XTENSA_CHIPS: [&str; 3] = ["esp32", "esp32s2", "esp32s3"];
+
+fn find_toolchain(tools_info: &ToolsInfo, chip: &str) -> Result<ToolchainSpec> {
+    // The gcc toolchains are the tools whose version command is the gcc executable,
+    // other tools (gdb, the ULP toolchain, clang) are not considered.
+    let toolchains = tools_info
+        .tools
+        .iter()
+        .filter_map(|tool| {
+            let compiler = tool.version_cmd.first()?;
+            compiler.ends_with("-gcc").then(|| (tool, compiler))
+        })
+        .collect::<Vec<_>>();
+
+    let supported_targets = |tool: &ToolInfo| -> Vec<String> {
+        tool.supported_targets
+            .as_ref()
+            .map(|targets| targets.to_vec())
+            .unwrap_or_default()
+    };
+
+    // The riscv toolchain also lists the xtensa chips with a ULP-RISC-V coprocessor
+    // (ex. `esp32s3`), so the toolchain must be selected by the architecture of the
+    // chip.
+    let arch_prefix = if XTENSA_CHIPS.contains(&chip) {
+        "xtensa-"
+    } else {
+        "riscv32-"
+    };
+
+    let (tool, compiler) = toolchains
+        .iter()
+        .filter(|(_, compiler)| compiler.starts_with(arch_prefix))
+        .find(|(tool, _)| supported_targets(tool).iter().any(|t| t == chip))
+        .ok_or_else(|| {
+            let mut chips = toolchains
+                .iter()
+                .flat_map(|(tool, _)| supported_targets(tool))
+                .filter(|t| t != "all")
+                .collect::<Vec<_>>();
+            chips.sort();
+            chips.dedup();
+
+            anyhow!(
+                "Unknown chip '{chip}', available chips: {}",
+                chips.join(", ")
+            )
+        })?;
+    let tool_name = tool.name.clone().unwrap_or_default();
+
+    let version = tool
+        .versions
+        .iter()
+        .find(|v| v.status == Some(tools_schema::VersionInfoStatus::Recommended))
+        .ok_or_else(|| {
+            let versions = tool
+                .versions
+                .iter()
+                .filter_map(|v| v.name.as_deref())
+                .collect::<Vec<_>>();
+            anyhow!(
+                "Tool '{tool_name}' has no recommended version, available versions: {}",
+                versions.join(", ")
+            )
+        })?;
+    let version_name = version.name.clone().unwrap_or_default();
+
+    let download = host_download_info(version).ok_or_else(|| {
+        anyhow!(
+            "Tool '{tool_name}' {version_name} has no download for the host platform ({}-{})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+    })?;
+
+    // The xtensa toolchain covers multiple chips, but its version command only names
+    // the compiler of one of them; each chip has its own `xtensa-<chip>-elf-gcc`.
+    let compiler = if compiler.starts_with("xtensa-") {
+        format!("xtensa-{chip}-elf-gcc")
+    } else {
+        (*compiler).clone()
+    };
+
+    Ok(ToolchainSpec {
+        tool_name,
+        version: version_name,
+        compiler,
+        download: ToolchainDownload {
+            url: download.url,
+            sha256: download.sha256,
+            size: u64::try_from(download.size).context("Invalid download size")?,
+        },
+    })
+}
+
 /// The error returned by [`EspIdf::try_from_env`].
 #[derive(Debug, thiserror::Error)]
 pub enum FromEnvError {
@@ -547,6 +809,54 @@ impl EspIdfVersion {
         }
     }
 
+    /// Whether this version satisfies the version `requirement`.
+    ///
+    /// The requirement is a `,`-separated list of comparisons which all must hold. Each
+    /// comparison is one of `>=`, `>`, `<=`, `<`, `=` (or no operator, which is the same as
+    /// `=`) followed by a `[v]<major>[.<minor>[.<patch>]]` version. Missing components
+    /// are treated as `0`, except for `=` where they match any value (ex. `=5.1` matches
+    /// `5.1.3`).
+    pub fn satisfies(&self, requirement: &str) -> Result<bool> {
+        requirement
+            .split(',')
+            .map(str::trim)
+            .filter(|comparison| !comparison.is_empty())
+            .try_fold(true, |satisfied, comparison| {
+                let (op, version) = ["<=", ">=", "<", ">", "="]
+                    .iter()
+                    .find_map(|op| Some((*op, comparison.strip_prefix(op)?)))
+                    .unwrap_or(("=", comparison));
+                let version = version.trim();
+                let version = version.strip_prefix('v').unwrap_or(version);
+
+                let parts = version
+                    .split('.')
+                    .map(|part| part.trim().parse::<u64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()
+                    .filter(|parts| (1..=3).contains(&parts.len()))
+                    .ok_or_else(|| anyhow!("invalid esp-idf version requirement '{comparison}'"))?;
+
+                let this = [self.major, self.minor, self.patch];
+                let ordering = this[..parts.len()].cmp(&parts[..]);
+                let ordering = if op == "=" {
+                    ordering
+                } else {
+                    this.cmp(&[0, 1, 2].map(|i| parts.get(i).copied().unwrap_or(0)))
+                };
+
+                let matches = match op {
+                    "<=" => ordering.is_le(),
+                    ">=" => ordering.is_ge(),
+                    "<" => ordering.is_lt(),
+                    ">" => ordering.is_gt(),
+                    _ => ordering.is_eq(),
+                };
+
+                Ok(satisfied && matches)
+            })
+    }
+
     /// Format an [`EspIdfVersion`] [`Result`] (e.g. from [`EspIdfVersion::try_from`]).
     pub fn format(ver: &Result<EspIdfVersion>) -> String {
         match ver {
@@ -588,10 +898,260 @@ pub enum EspIdfOrigin {
 /// A distinct version of the esp-idf repository to be installed.
 pub type EspIdfRemote = git::sdk::RemoteSdk;
 
+/// Rewrite rules for routing all tool downloads and git clones through a mirror.
+///
+/// The rules are the ones of the esp-idf's own installer (`idf_tools.py`):
+/// - The prefix map (see [`IDF_MIRROR_PREFIX_MAP_VAR`]) is a `;`-separated list of
+///   `<pattern>,<replacement>` rules. A url is rewritten by the first rule whose regex
+///   `pattern` changes it, the `replacement` is inserted literally.
+/// - The github assets mirror (see [`IDF_GITHUB_ASSETS_VAR`]) then replaces
+///   `https://github.com/` with `https://<github assets mirror>/`.
+///
+/// Git urls are only rewritten by the prefix map, as the github assets mirror only
+/// serves release assets (see [`rewrite_git_url`](Self::rewrite_git_url)).
+#[derive(Debug, Clone, Default)]
+pub struct DownloadMirror {
+    prefix_map: Option<String>,
+    rules: Vec<(regex::Regex, String)>,
+    github_assets: Option<String>,
+}
+
+impl DownloadMirror {
+    /// Create a mirror that replaces `https://github.com/` with `https://<github_assets>/`
+    /// (ex. `dl.espressif.com/github_assets`).
+    pub fn github_assets(github_assets: &str) -> Result<Self> {
+        Self::default().with_github_assets(github_assets)
+    }
+
+    /// Create a mirror from a `;`-separated list of `<pattern>,<replacement>` rules.
+    ///
+    /// Rules without a `,` are ignored with a warning, same as `idf_tools.py` does.
+    pub fn from_prefix_map(prefix_map: &str) -> Result<Self> {
+        let rules = prefix_map
+            .split(';')
+            .filter(|rule| !rule.is_empty())
+            .filter_map(|rule| match rule.split_once(',') {
+                Some(rule) => Some(rule),
+                None => {
+                    cargo::print_warning(format_args!(
+                        "Ignoring mirror rule '{rule}' of {IDF_MIRROR_PREFIX_MAP_VAR}: \
+                         expected '<pattern>,<replacement>'"
+                    ));
+                    None
+                }
+            })
+            .map(|(pattern, replacement)| {
+                let pattern = regex::Regex::new(pattern)
+                    .with_context(|| anyhow!("invalid mirror rule pattern '{pattern}'"))?;
+
+                Ok((pattern, replacement.to_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            prefix_map: Some(prefix_map.to_owned()),
+            rules,
+            github_assets: None,
+        })
+    }
+
+    /// Additionally replace `https://github.com/` with `https://<github_assets>/` after
+    /// the prefix map rules were applied.
+    pub fn with_github_assets(mut self, github_assets: &str) -> Result<Self> {
+        if github_assets.contains("://") {
+            bail!(
+                "invalid github assets mirror '{github_assets}': it must not contain a url \
+                 scheme (ex. 'dl.espressif.com/github_assets')"
+            );
+        }
+
+        self.github_assets = Some(github_assets.to_owned());
+        Ok(self)
+    }
+
+    /// Create a mirror from the [`IDF_MIRROR_PREFIX_MAP_VAR`] and
+    /// [`IDF_GITHUB_ASSETS_VAR`] environment variables.
+    ///
+    /// Returns [`None`] if neither variable is set or empty.
+    pub fn try_from_env() -> Result<Option<Self>> {
+        let prefix_map = env::var(IDF_MIRROR_PREFIX_MAP_VAR)
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+        let github_assets = env::var(IDF_GITHUB_ASSETS_VAR)
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let mirror = match prefix_map {
+            Some(prefix_map) => Self::from_prefix_map(&prefix_map)?,
+            None if github_assets.is_some() => Self::default(),
+            None => return Ok(None),
+        };
+        let mirror = match github_assets {
+            Some(github_assets) => mirror.with_github_assets(&github_assets)?,
+            None => mirror,
+        };
+
+        Ok(Some(mirror))
+    }
+
+    /// Rewrite `url` with the first rule that changes it and then the github assets
+    /// mirror, or return it unchanged if nothing matches.
+    pub fn rewrite_url(&self, url: &str) -> String {
+        let url = self.rewrite_git_url(url);
+
+        match self.github_assets_url() {
+            Some(github_assets) => match url.strip_prefix("https://github.com/") {
+                Some(path) => format!("{github_assets}{path}"),
+                None => url,
+            },
+            None => url,
+        }
+    }
+
+    /// Rewrite the git repository `url` with the first rule of the prefix map that
+    /// changes it, or return it unchanged if no rule matches.
+    pub fn rewrite_git_url(&self, url: &str) -> String {
+        self.rules
+            .iter()
+            .map(|(pattern, replacement)| pattern.replace_all(url, regex::NoExpand(replacement)))
+            .find(|new_url| new_url != url)
+            .map(|new_url| new_url.into_owned())
+            .unwrap_or_else(|| url.to_owned())
+    }
+
+    /// Whether the hosts of all rule replacements and the github assets mirror accept
+    /// connections.
+    pub fn is_reachable(&self) -> bool {
+        self.rules
+            .iter()
+            .map(|(_, replacement)| replacement.clone())
+            .chain(self.github_assets_url())
+            .all(|url| is_url_reachable(&url))
+    }
+
+    /// `https://<github assets mirror>/`
+    fn github_assets_url(&self) -> Option<String> {
+        self.github_assets
+            .as_ref()
+            .map(|github_assets| format!("https://{}/", github_assets.trim().trim_end_matches('/')))
+    }
+
+    /// The environment variables passed to `idf_tools.py` for this mirror.
+    ///
+    /// These are the unchanged values the mirror was created from, [`None`] if the
+    /// variable must be removed.
+    fn env_vars(&self) -> [(&'static str, Option<&str>); 2] {
+        [
+            (IDF_MIRROR_PREFIX_MAP_VAR, self.prefix_map.as_deref()),
+            (IDF_GITHUB_ASSETS_VAR, self.github_assets.as_deref()),
+        ]
+    }
+}
+
+impl std::fmt::Display for DownloadMirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let vars = self
+            .env_vars()
+            .into_iter()
+            .filter_map(|(var, value)| Some(format!("{var}={}", value?)))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", vars.join(" "))
+    }
+}
+
+/// Whether a TCP connection can be established to the host of the http(s) `url`.
+fn is_url_reachable(url: &str) -> bool {
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    let (rest, default_port) = if let Some(rest) = url.strip_prefix("https://") {
+        (rest, 443)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (rest, 80)
+    } else {
+        log::debug!("Cannot check reachability of non-http url '{url}'");
+        return false;
+    };
+
+    let authority = rest.split('/').next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let addr = if authority.contains(':') {
+        authority.to_owned()
+    } else {
+        format!("{authority}:{default_port}")
+    };
+
+    addr.to_socket_addrs()
+        .map(|mut addrs| addrs.any(|addr| TcpStream::connect_timeout(&addr, TIMEOUT).is_ok()))
+        .unwrap_or(false)
+}
+
+/// The mirror used by [`Installer::install`], which is only checked for reachability
+/// when something is downloaded through it.
+struct InstallMirror {
+    mirror: Option<DownloadMirror>,
+    checked: bool,
+}
+
+impl InstallMirror {
+    fn new(mirror: Option<DownloadMirror>) -> Self {
+        Self {
+            mirror,
+            checked: false,
+        }
+    }
+
+    /// The mirror to download through, or [`None`] if there is none or it is not
+    /// reachable.
+    ///
+    /// A warning is printed if the mirror is not reachable.
+    fn reachable(&mut self) -> Option<&DownloadMirror> {
+        if !self.checked {
+            self.checked = true;
+
+            if let Some(mirror) = self.mirror.take() {
+                if mirror.is_reachable() {
+                    self.mirror = Some(mirror);
+                } else {
+                    cargo::print_warning(format_args!(
+                        "Download mirror '{mirror}' is not reachable, falling back to the \
+                         default download locations"
+                    ));
+                }
+            }
+        }
+
+        self.mirror.as_ref()
+    }
+
+    /// The mirror without checking its reachability.
+    fn unchecked(&self) -> Option<&DownloadMirror> {
+        self.mirror.as_ref()
+    }
+}
+
+/// Set the mirror variables of `idf_tools.py` in `cmd` to the values of `mirror` (which
+/// are the unchanged user variables if it was read from the environment), or remove
+/// them if no mirror is used.
+fn apply_mirror_env(cmd: &mut cmd::Cmd, mirror: Option<&DownloadMirror>) {
+    let mirror_env = mirror.map(DownloadMirror::env_vars).unwrap_or([
+        (IDF_MIRROR_PREFIX_MAP_VAR, None),
+        (IDF_GITHUB_ASSETS_VAR, None),
+    ]);
+
+    for (var, value) in mirror_env {
+        match value {
+            Some(value) => cmd.env(var, value),
+            None => cmd.env_remove(var),
+        };
+    }
+}
+
 /// Installer for the esp-idf source and tools.
 pub struct Installer {
     esp_idf_origin: EspIdfOrigin,
     custom_install_dir: Option<PathBuf>,
+    mirror: Option<DownloadMirror>,
     #[allow(clippy::type_complexity)]
     tools_provider:
         Option<Box<dyn FnOnce(&SourceTree, &Result<EspIdfVersion>) -> Result<Vec<Tools>>>>,
@@ -604,6 +1164,7 @@ impl Installer {
             esp_idf_origin,
             tools_provider: None,
             custom_install_dir: None,
+            mirror: None,
         }
     }
 
@@ -626,6 +1187,16 @@ impl Installer {
         self
     }
 
+    /// Route all tool downloads and the esp-idf git clone through `mirror`.
+    ///
+    /// If [`None`] the mirror is read from the environment (see
+    /// [`DownloadMirror::try_from_env`]).
+    #[must_use]
+    pub fn mirror(mut self, mirror: Option<DownloadMirror>) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
     /// Install the esp-idf source if a managed ESP-IDF reference was supplied by the user and then install all tools added with [`with_tools`](Self::with_tools).
     ///
     /// The install directory, where the esp-idf source and tools are installed into, is
@@ -647,11 +1218,22 @@ impl Installer {
     ///    <tools...>` per [`Tools`] instance added with [`with_tools`](Self::with_tools).
     ///    `tools_json` is the optional [`Tools::index`] path, if [`None`] the `tools.json`
     ///    of the esp-idf is used.
+    ///
+    /// If a [`mirror`](Self::mirror) is configured (or found in the environment), it is
+    /// checked for reachability before the esp-idf is cloned or tools are installed. If
+    /// it is not reachable, a warning is printed and the default download locations are
+    /// used. The remote of the esp-idf clone is always the original repository url, so
+    /// that an existing clone is kept if the mirror changes.
     pub fn install(self) -> Result<EspIdf> {
         let install_dir = self
             .custom_install_dir
             .unwrap_or_else(Self::global_install_dir);
 
+        let mut mirror = InstallMirror::new(match self.mirror {
+            Some(mirror) => Some(mirror),
+            None => DownloadMirror::try_from_env()?,
+        });
+
         std::fs::create_dir_all(&install_dir).with_context(|| {
             format!(
                 "could not create esp-idf install dir '{}'",
@@ -660,15 +1242,32 @@ impl Installer {
         })?;
 
         let (esp_idf_dir, managed_repo) = match self.esp_idf_origin {
-            EspIdfOrigin::Managed(managed) => (
-                SourceTree::Git(managed.open_or_clone(
-                    &install_dir,
-                    git::CloneOptions::new().depth(1),
-                    DEFAULT_ESP_IDF_REPOSITORY,
-                    MANAGED_ESP_IDF_REPOS_DIR_BASE,
-                )?),
-                true,
-            ),
+            EspIdfOrigin::Managed(managed) => {
+                let mut options = git::CloneOptions::new().depth(1);
+
+                // Only a new clone is downloaded through the mirror.
+                let repo_path = managed.repo_path(&install_dir, MANAGED_ESP_IDF_REPOS_DIR_BASE);
+                if !repo_path.join(".git").exists() {
+                    let repo_url = managed
+                        .repo_url
+                        .as_deref()
+                        .unwrap_or(DEFAULT_ESP_IDF_REPOSITORY);
+                    let mirror_url = mirror.reachable().map(|m| m.rewrite_git_url(repo_url));
+                    if let Some(mirror_url) = mirror_url.filter(|url| url != repo_url) {
+                        options = options.mirror_url(mirror_url);
+                    }
+                }
+
+                (
+                    SourceTree::Git(managed.open_or_clone(
+                        &install_dir,
+                        options,
+                        DEFAULT_ESP_IDF_REPOSITORY,
+                        MANAGED_ESP_IDF_REPOS_DIR_BASE,
+                    )?),
+                    true,
+                )
+            }
             EspIdfOrigin::Custom(tree) => (tree, false),
         };
         // Reading the version out of a cmake build file
@@ -690,8 +1289,10 @@ impl Installer {
 
         // assumes that the command can be run repeatedly
         // whenalready installed -> checks for updates and a working state
-        cmd!(PYTHON, &idf_tools_py, "--idf-path", esp_idf_dir.path(), "--non-interactive", "install-python-env";
-        env=(IDF_TOOLS_PATH_VAR, &install_dir), env_remove=("MSYSTEM"), env_remove=(IDF_PYTHON_ENV_PATH_VAR)).run()?;
+        let mut install_python_env = cmd!(PYTHON, &idf_tools_py, "--idf-path", esp_idf_dir.path(), "--non-interactive", "install-python-env";
+        env=(IDF_TOOLS_PATH_VAR, &install_dir), env_remove=("MSYSTEM"), env_remove=(IDF_PYTHON_ENV_PATH_VAR));
+        apply_mirror_env(&mut install_python_env, mirror.unchecked());
+        install_python_env.run()?;
 
         // since the above command exited sucessfully -> there should be a virt_env dir
 
@@ -741,9 +1342,19 @@ impl Installer {
 
         //let tools_vec = parse_into_tools(tools_wanted, tools_json, install_dir.clone())?;
 
-        let all_tools_installed = tools_vec.iter().all(|tool| tool.test());
+        // A tool without install marker was not installed completely (ex. because
+        // extracting its archive was interrupted), so clear it and install it again.
+        let shared_install_dir = install_dir == Self::global_install_dir();
+        let mut all_tools_installed = true;
+        for tool in &tools_vec {
+            if tool.remove_if_incomplete(shared_install_dir)? || !tool.is_complete() || !tool.test()
+            {
+                all_tools_installed = false;
+            }
+        }
 
         if !all_tools_installed {
+            let mirror = mirror.reachable();
             for tool_set in tools {
                 let tools_json = tool_set
                     .index
@@ -754,8 +1365,10 @@ impl Installer {
                     .into_iter()
                     .flatten();
 
-                cmd!(&venv_python, &idf_tools_py, "--idf-path", esp_idf_dir.path(), @tools_json.clone(), "install"; 
-                     env=(IDF_TOOLS_PATH_VAR, &install_dir), args=(tool_set.tools)).run()?;
+                let mut install = cmd!(&venv_python, &idf_tools_py, "--idf-path", esp_idf_dir.path(), @tools_json.clone(), "install";
+                     env=(IDF_TOOLS_PATH_VAR, &install_dir), args=(tool_set.tools));
+                apply_mirror_env(&mut install, mirror);
+                install.run()?;
             }
 
             // Test again if all tools are now installed correctly
@@ -763,6 +1376,21 @@ impl Installer {
             if !all_tools_installed {
                 return Err(anyhow::Error::msg("Could not install all requested Tools"));
             }
+
+            for tool in tools_vec
+                .iter()
+                .filter(|tool| !tool.installed_marker().exists())
+            {
+                tool.write_installed_marker()?;
+
+                if !tool.is_complete() {
+                    return Err(anyhow!(
+                        "Tool '{}' was installed to '{}' but is missing its expected content",
+                        tool.name,
+                        tool.version_dir().display()
+                    ));
+                }
+            }
         }
 
         // End Tools install
@@ -957,3 +1585,293 @@ pub mod sysenv {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toolchain_for_chip() {
+        let tool = |name: &str, cmd: &str, targets: &[&str]| {
+            serde_json::json!({
+                "name": name,
+                "description": "",
+                "export_paths": [[name, "bin"]],
+                "info_url": "",
+                "install": "always",
+                "license": "GPL-3.0-with-GCC-exception",
+                "supported_targets": targets,
+                "version_cmd": [cmd, "--version"],
+                "version_regex": "",
+                "versions": [
+                    { "name": "12.2.0_20230208", "status": "supported", "any": {
+                        "sha256": "00", "size": 1, "url": "https://example.com/old.tar.xz"
+                    }},
+                    { "name": "13.2.0_20230928", "status": "recommended", "any": {
+                        "sha256": "ab", "size": 42, "url": "https://example.com/new.tar.xz"
+                    }}
+                ]
+            })
+        };
+        let tools_info: ToolsInfo = serde_json::from_value(serde_json::json!({
+            "version": 2,
+            "tools": [
+                tool("xtensa-esp-elf-gdb", "xtensa-esp32-elf-gdb", &["esp32", "esp32s3"]),
+                tool("esp32ulp-elf", "esp32ulp-elf-as", &["esp32", "esp32s3"]),
+                // Also used for the ULP-RISC-V coprocessor of the esp32s2 and esp32s3.
+                tool(
+                    "riscv32-esp-elf",
+                    "riscv32-esp-elf-gcc",
+                    &["esp32c3", "esp32c6", "esp32s2", "esp32s3"],
+                ),
+                tool("xtensa-esp-elf", "xtensa-esp32-elf-gcc", &["esp32", "esp32s2", "esp32s3"]),
+                tool("esp-clang", "clang", &["all"]),
+            ]
+        }))
+        .unwrap();
+
+        let spec = find_toolchain(&tools_info, "esp32c3").unwrap();
+        assert_eq!(
+            spec,
+            ToolchainSpec {
+                tool_name: "riscv32-esp-elf".into(),
+                version: "13.2.0_20230928".into(),
+                compiler: "riscv32-esp-elf-gcc".into(),
+                download: ToolchainDownload {
+                    url: "https://example.com/new.tar.xz".into(),
+                    sha256: "ab".into(),
+                    size: 42,
+                },
+            }
+        );
+        let spec = find_toolchain(&tools_info, "esp32s3").unwrap();
+        assert_eq!(spec.tool_name, "xtensa-esp-elf");
+        assert_eq!(spec.compiler, "xtensa-esp32s3-elf-gcc");
+        assert_eq!(
+            find_toolchain(&tools_info, "esp32").unwrap().compiler,
+            "xtensa-esp32-elf-gcc"
+        );
+        assert_eq!(
+            find_toolchain(&tools_info, "esp32s2").unwrap().tool_name,
+            "xtensa-esp-elf"
+        );
+
+        let err = find_toolchain(&tools_info, "esp8266").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown chip 'esp8266', available chips: esp32, esp32c3, esp32c6, esp32s2, esp32s3"
+        );
+    }
+
+    #[test]
+    fn esp_idf_version_satisfies() {
+        let version = EspIdfVersion {
+            major: 5,
+            minor: 1,
+            patch: 3,
+        };
+
+        assert!(version.satisfies("5.1").unwrap());
+        assert!(version.satisfies("=v5").unwrap());
+        assert!(version.satisfies(">=5.1.3").unwrap());
+        assert!(version.satisfies(">=4.4, <5.2").unwrap());
+        assert!(version.satisfies(">5.1").unwrap());
+        assert!(!version.satisfies("<5.1").unwrap());
+        assert!(!version.satisfies("5.2").unwrap());
+        assert!(version.satisfies("").unwrap());
+        assert!(version.satisfies(">=five").is_err());
+    }
+
+    #[test]
+    fn partially_installed_tool_is_removed() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let tool = Tool {
+            name: "riscv32-esp-elf".into(),
+            version: "esp-13.2.0_20230928".into(),
+            install_dir: install_dir.path().to_owned(),
+            export_path: path_buf![
+                "tools",
+                "riscv32-esp-elf",
+                "esp-13.2.0_20230928",
+                "riscv32-esp-elf",
+                "bin"
+            ],
+            ..Default::default()
+        };
+        let version_dir = tool.version_dir();
+
+        // Extraction was interrupted before the marker was written.
+        fs::create_dir_all(version_dir.join("riscv32-esp-elf")).unwrap();
+        assert!(!tool.is_complete());
+        assert!(!tool.remove_if_incomplete(true).unwrap());
+        assert!(version_dir.exists());
+        assert!(tool.remove_if_incomplete(false).unwrap());
+        assert!(!version_dir.exists());
+
+        // The marker exists but the expected top-level entry is missing.
+        fs::create_dir_all(&version_dir).unwrap();
+        tool.write_installed_marker().unwrap();
+        assert!(!tool.is_complete());
+
+        fs::create_dir_all(tool.abs_export_path()).unwrap();
+        assert!(tool.is_complete());
+        assert!(!tool.remove_if_incomplete(false).unwrap());
+        assert!(version_dir.exists());
+    }
+
+    #[test]
+    fn tool_without_marker_is_only_kept_in_shared_install_dir() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let tool = Tool {
+            name: "esp32ulp-elf".into(),
+            version: "2.35_20220830".into(),
+            install_dir: install_dir.path().to_owned(),
+            export_path: path_buf![
+                "tools",
+                "esp32ulp-elf",
+                "2.35_20220830",
+                "esp32ulp-elf",
+                "bin"
+            ],
+            ..Default::default()
+        };
+
+        // Installed into the global install dir by `idf_tools.py`, which does not write a
+        // marker.
+        fs::create_dir_all(tool.abs_export_path()).unwrap();
+        assert!(tool.test());
+        assert!(!tool.is_complete());
+
+        assert!(!tool.remove_if_incomplete(true).unwrap());
+        assert!(tool.version_dir().exists());
+        assert!(tool.is_complete());
+
+        // In an install dir of embuild, a missing marker means the installation was
+        // interrupted, even if the tool passes its test.
+        fs::remove_file(tool.installed_marker()).unwrap();
+        assert!(tool.remove_if_incomplete(false).unwrap());
+        assert!(!tool.version_dir().exists());
+    }
+
+    #[test]
+    fn tool_with_missing_binary_is_not_installed() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let tool = Tool {
+            name: "xtensa-esp-elf".into(),
+            version: "esp-13.2.0_20230928".into(),
+            install_dir: install_dir.path().to_owned(),
+            export_path: path_buf![
+                "tools",
+                "xtensa-esp-elf",
+                "esp-13.2.0_20230928",
+                "xtensa-esp-elf",
+                "bin"
+            ],
+            version_cmd_args: vec!["xtensa-esp32-elf-gcc".into(), "--version".into()],
+            version_regex: r"\d+\.\d+\.\d+".into(),
+            ..Default::default()
+        };
+
+        // Extraction was interrupted after the `bin` dir but before the compiler.
+        fs::create_dir_all(tool.abs_export_path()).unwrap();
+        assert!(!tool.test());
+
+        assert!(!tool.remove_if_incomplete(true).unwrap());
+        assert!(!tool.installed_marker().exists());
+        assert!(tool.remove_if_incomplete(false).unwrap());
+        assert!(!tool.version_dir().exists());
+    }
+
+    #[test]
+    fn download_mirror_rewrite_url() {
+        let mirror = DownloadMirror::github_assets("dl.espressif.com/github_assets/").unwrap();
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/espressif/crosstool-NG/releases/gcc.tar.xz"),
+            "https://dl.espressif.com/github_assets/espressif/crosstool-NG/releases/gcc.tar.xz"
+        );
+        assert_eq!(
+            mirror.rewrite_url("https://dl.espressif.com/dl/cmake.zip"),
+            "https://dl.espressif.com/dl/cmake.zip"
+        );
+
+        let mirror = DownloadMirror::from_prefix_map(
+            "^https://github.com/espressif/esp-idf,https://mirror.local/esp-idf;\
+             ^https://github.com/,https://assets.local/",
+        )
+        .unwrap();
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/espressif/openocd-esp32/a.zip"),
+            "https://assets.local/espressif/openocd-esp32/a.zip"
+        );
+
+        // Replacements are literal, like in `idf_tools.py`.
+        let mirror =
+            DownloadMirror::from_prefix_map("^https://(github).com/,https://$1.local/").unwrap();
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/a.zip"),
+            "https://$1.local/a.zip"
+        );
+
+        let mirror = DownloadMirror::from_prefix_map("^https://github.com/").unwrap();
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/a.zip"),
+            "https://github.com/a.zip"
+        );
+
+        assert!(DownloadMirror::github_assets("https://dl.espressif.com").is_err());
+    }
+
+    #[test]
+    fn download_mirror_prefix_map_and_github_assets() {
+        let mirror = DownloadMirror::from_prefix_map(
+            "^https://dl.espressif.com/dl/,https://dl.mirror.local/",
+        )
+        .unwrap()
+        .with_github_assets("assets.mirror.local")
+        .unwrap();
+
+        assert_eq!(
+            mirror.rewrite_url("https://dl.espressif.com/dl/cmake.zip"),
+            "https://dl.mirror.local/cmake.zip"
+        );
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/espressif/crosstool-NG/gcc.tar.xz"),
+            "https://assets.mirror.local/espressif/crosstool-NG/gcc.tar.xz"
+        );
+
+        // The github assets mirror is applied to the result of the prefix map.
+        let mirror = DownloadMirror::from_prefix_map(
+            "^https://github.com/espressif/esp-idf,https://github.com/mirror/esp-idf",
+        )
+        .unwrap()
+        .with_github_assets("assets.mirror.local/")
+        .unwrap();
+        assert_eq!(
+            mirror.rewrite_url("https://github.com/espressif/esp-idf/releases/a.zip"),
+            "https://assets.mirror.local/mirror/esp-idf/releases/a.zip"
+        );
+        // Git urls are only rewritten by the prefix map.
+        assert_eq!(
+            mirror.rewrite_git_url(DEFAULT_ESP_IDF_REPOSITORY),
+            "https://github.com/mirror/esp-idf.git"
+        );
+        assert_eq!(
+            DownloadMirror::github_assets("assets.mirror.local")
+                .unwrap()
+                .rewrite_git_url(DEFAULT_ESP_IDF_REPOSITORY),
+            DEFAULT_ESP_IDF_REPOSITORY
+        );
+
+        // The original values are passed on to `idf_tools.py`.
+        assert_eq!(
+            mirror.env_vars(),
+            [
+                (
+                    IDF_MIRROR_PREFIX_MAP_VAR,
+                    Some("^https://github.com/espressif/esp-idf,https://github.com/mirror/esp-idf")
+                ),
+                (IDF_GITHUB_ASSETS_VAR, Some("assets.mirror.local/"))
+            ]
+        );
+    }
+}
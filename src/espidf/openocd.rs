@@ -0,0 +1,299 @@
+//! OpenOCD configuration for JTAG debugging of esp chips.
+//!
+//! Requires the esp fork of OpenOCD (`openocd-esp32`), which is installed as part of the
+//! esp-idf tools and provides the interface and target scripts used here.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use strum::{Display, EnumIter, EnumString, IntoStaticStr};
+
+use crate::fs;
+
+/// An esp chip that can be debugged with OpenOCD.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, EnumString, Display, EnumIter, IntoStaticStr)]
+#[strum(ascii_case_insensitive)]
+pub enum Chip {
+    #[strum(serialize = "esp32")]
+    Esp32,
+    #[strum(serialize = "esp32s2")]
+    Esp32S2,
+    #[strum(serialize = "esp32s3")]
+    Esp32S3,
+    #[strum(serialize = "esp32c3")]
+    Esp32C3,
+    #[strum(serialize = "esp32h2")]
+    Esp32H2,
+}
+
+impl Chip {
+    /// The OpenOCD target script of this chip.
+    pub fn target_script(self) -> &'static str {
+        match self {
+            Self::Esp32 => "target/esp32.cfg",
+            Self::Esp32S2 => "target/esp32s2.cfg",
+            Self::Esp32S3 => "target/esp32s3.cfg",
+            Self::Esp32C3 => "target/esp32c3.cfg",
+            Self::Esp32H2 => "target/esp32h2.cfg",
+        }
+    }
+
+    /// Whether this chip has more than one core.
+    pub fn is_multi_core(self) -> bool {
+        matches!(self, Self::Esp32 | Self::Esp32S3)
+    }
+
+    /// Whether this chip has a built-in USB-JTAG adapter.
+    pub fn has_usb_jtag(self) -> bool {
+        matches!(self, Self::Esp32S3 | Self::Esp32C3 | Self::Esp32H2)
+    }
+}
+
+/// The JTAG adapter OpenOCD connects to.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Interface {
+    /// An FTDI FT2232H based adapter, like the ESP-Prog or the one on the ESP-WROVER-KIT.
+    Ftdi,
+    /// The built-in USB-JTAG adapter of the chip (see [`Chip::has_usb_jtag`]).
+    UsbJtag,
+    /// A custom OpenOCD interface script.
+    Custom(PathBuf),
+}
+
+impl Interface {
+    /// The OpenOCD interface script of this adapter.
+    pub fn script(&self) -> &Path {
+        match self {
+            Self::Ftdi => Path::new("interface/ftdi/esp32_devkitj_v1.cfg"),
+            Self::UsbJtag => Path::new("interface/esp_usb_jtag.cfg"),
+            Self::Custom(script) => script,
+        }
+    }
+}
+
+/// The OpenOCD configuration for debugging a chip over JTAG.
+///
+/// ```ignore
+/// let config = OpenOcdConfig::new(Chip::Esp32C3, Interface::UsbJtag);
+/// config.write_script(Path::new("openocd.cfg"))?;
+/// // openocd -f openocd.cfg
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[must_use]
+pub struct OpenOcdConfig {
+    /// The chip to debug.
+    pub chip: Chip,
+    /// The adapter to connect to the chip.
+    pub interface: Interface,
+    /// A board script used instead of the interface and target scripts.
+    ///
+    /// Board scripts already source the interface and target scripts of the board, so if
+    /// this is set [`interface`](Self::interface) and the target script of
+    /// [`chip`](Self::chip) are not used.
+    pub board_script: Option<PathBuf>,
+    /// Whether all cores of a multi-core chip are debugged (`true` by default).
+    ///
+    /// If `false`, only the first core is debugged. Has no effect on single-core chips.
+    pub smp: bool,
+}
+
+impl OpenOcdConfig {
+    /// Create a configuration for debugging `chip` with the adapter `interface`.
+    pub fn new(chip: Chip, interface: Interface) -> Self {
+        Self {
+            chip,
+            interface,
+            board_script: None,
+            smp: true,
+        }
+    }
+
+    /// Use the board script `script` instead of the interface and target scripts.
+    pub fn with_board_script(mut self, script: impl Into<PathBuf>) -> Self {
+        self.board_script = Some(script.into());
+        self
+    }
+
+    /// Set whether all cores of a multi-core chip are debugged.
+    pub fn with_smp(mut self, smp: bool) -> Self {
+        self.smp = smp;
+        self
+    }
+
+    /// Get the OpenOCD command-line arguments for this configuration.
+    ///
+    /// Fails if the chip cannot be debugged with the adapter (see [`Self::validate`]).
+    pub fn to_openocd_args(&self) -> Result<Vec<String>> {
+        self.validate()?;
+
+        let mut args = Vec::new();
+
+        for command in self.commands() {
+            args.push("-c".to_owned());
+            args.push(command.to_owned());
+        }
+        for script in self.scripts() {
+            args.push("-f".to_owned());
+            args.push(script.display().to_string());
+        }
+
+        Ok(args)
+    }
+
+    /// Write this configuration as an OpenOCD script to `path`, which can then be passed
+    /// to `openocd -f <path>`.
+    ///
+    /// Fails if the chip cannot be debugged with the adapter (see [`Self::validate`]).
+    pub fn write_script(&self, path: &Path) -> Result<()> {
+        self.validate()?;
+
+        fs::atomic_write(path, self.to_script()).with_context(|| {
+            anyhow!(
+                "Failed to write OpenOCD configuration to '{}'",
+                path.display()
+            )
+        })
+    }
+
+    /// Check that the chip can be debugged with the adapter, i.e. that the chip has a
+    /// built-in USB-JTAG adapter if [`Interface::UsbJtag`] is used.
+    ///
+    /// A [`board_script`](Self::board_script) is not checked, as it selects the adapter
+    /// itself.
+    pub fn validate(&self) -> Result<()> {
+        if self.board_script.is_none()
+            && self.interface == Interface::UsbJtag
+            && !self.chip.has_usb_jtag()
+        {
+            bail!(
+                "Chip {} has no built-in USB-JTAG adapter, use another interface",
+                self.chip
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Get the contents of the OpenOCD script for this configuration.
+    fn to_script(&self) -> String {
+        let mut script = format!("# OpenOCD configuration for {}\n", self.chip);
+
+        for command in self.commands() {
+            let _ = writeln!(script, "{command}");
+        }
+        for file in self.scripts() {
+            let _ = writeln!(
+                script,
+                "source [find {}]",
+                tcl_word(&file.display().to_string())
+            );
+        }
+
+        script
+    }
+
+    /// All OpenOCD commands that must be run before the scripts are sourced.
+    fn commands(&self) -> Vec<&'static str> {
+        if self.chip.is_multi_core() && !self.smp {
+            vec!["set ESP_ONLYCPU 1"]
+        } else {
+            vec![]
+        }
+    }
+
+    /// All OpenOCD scripts of this configuration.
+    fn scripts(&self) -> Vec<&Path> {
+        match &self.board_script {
+            Some(board) => vec![board],
+            None => vec![
+                self.interface.script(),
+                Path::new(self.chip.target_script()),
+            ],
+        }
+    }
+}
+
+/// Quote `s` as a single Tcl word, if necessary.
+fn tcl_word(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || "{}[]$\\\";".contains(c)) {
+        s.to_owned()
+    } else {
+        format!("{{{s}}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openocd_args() {
+        let config = OpenOcdConfig::new(Chip::Esp32C3, Interface::UsbJtag);
+        assert_eq!(
+            config.to_openocd_args().unwrap(),
+            [
+                "-f",
+                "interface/esp_usb_jtag.cfg",
+                "-f",
+                "target/esp32c3.cfg"
+            ]
+        );
+
+        let config = OpenOcdConfig::new(Chip::Esp32, Interface::Ftdi).with_smp(false);
+        assert_eq!(
+            config.to_openocd_args().unwrap(),
+            [
+                "-c",
+                "set ESP_ONLYCPU 1",
+                "-f",
+                "interface/ftdi/esp32_devkitj_v1.cfg",
+                "-f",
+                "target/esp32.cfg"
+            ]
+        );
+
+        let config = OpenOcdConfig::new(Chip::Esp32S3, Interface::UsbJtag)
+            .with_board_script("board/esp32s3-builtin.cfg");
+        assert_eq!(
+            config.to_openocd_args().unwrap(),
+            ["-f", "board/esp32s3-builtin.cfg"]
+        );
+    }
+
+    #[test]
+    fn usb_jtag_requires_chip_support() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openocd.cfg");
+
+        for chip in [Chip::Esp32, Chip::Esp32S2] {
+            let config = OpenOcdConfig::new(chip, Interface::UsbJtag);
+            assert!(config.to_openocd_args().is_err());
+
+            assert!(config.write_script(&path).is_err());
+            assert!(!path.exists());
+
+            assert!(config
+                .with_board_script("board/custom.cfg")
+                .to_openocd_args()
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn openocd_script() {
+        let config = OpenOcdConfig::new(
+            Chip::Esp32S3,
+            Interface::Custom(PathBuf::from("/my adapters/probe.cfg")),
+        )
+        .with_smp(false);
+
+        assert_eq!(
+            config.to_script(),
+            "# OpenOCD configuration for esp32s3\n\
+             set ESP_ONLYCPU 1\n\
+             source [find {/my adapters/probe.cfg}]\n\
+             source [find target/esp32s3.cfg]\n"
+        );
+    }
+}
@@ -1,10 +1,12 @@
 //! Filesystem utilities.
 
-use std::fs::{self, File};
-use std::io::{self, Read};
-use std::path::Path;
+use std::fs::{self, File, Permissions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 
 /// Copy `src_file` to `dest_file_or_dir` if `src_file` is different or the destination
 /// file doesn't exist.
@@ -101,3 +103,142 @@ pub fn copy_with_metadata(src_file: impl AsRef<Path>, dest_file: impl AsRef<Path
 
     Ok(())
 }
+
+/// Write `contents` to `path` atomically.
+///
+/// The contents are first written to a temporary file in the same directory as `path`,
+/// which is then renamed to `path`. So if the process is interrupted, `path` either has
+/// its old or its new contents but is never only partially written. If `path` already
+/// exists, its permissions are kept.
+pub fn atomic_write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<()> {
+    atomic_write_impl(path.as_ref(), contents.as_ref(), None)
+}
+
+/// Write `contents` to `path` atomically and set its `permissions`.
+///
+/// See [`atomic_write`].
+pub fn atomic_write_with_permissions(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+    permissions: Permissions,
+) -> Result<()> {
+    atomic_write_impl(path.as_ref(), contents.as_ref(), Some(permissions))
+}
+
+fn atomic_write_impl(path: &Path, contents: &[u8], permissions: Option<Permissions>) -> Result<()> {
+    let temp_path = atomic_write_temp_path(path)?;
+
+    let result = (|| {
+        let mut temp_file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+        temp_file.write_all(contents)?;
+
+        let permissions = match permissions {
+            Some(permissions) => Some(permissions),
+            None => fs::metadata(path).ok().map(|m| m.permissions()),
+        };
+        if let Some(permissions) = permissions {
+            temp_file.set_permissions(permissions)?;
+        }
+        temp_file.sync_all()?;
+        drop(temp_file);
+
+        fs::rename(&temp_path, path).map_err(|err| {
+            if is_cross_device_error(&err) {
+                anyhow!(
+                    "Temporary file '{}' and '{}' are on different filesystems",
+                    temp_path.display(),
+                    path.display()
+                )
+            } else {
+                err.into()
+            }
+        })
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.with_context(|| anyhow!("Failed to write '{}'", path.display()))
+}
+
+/// Get a path for a temporary file next to `path` that is unique to this process.
+fn atomic_write_temp_path(path: &Path) -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("'{}' is not a file path", path.display()))?;
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    Ok(path.with_file_name(temp_name))
+}
+
+/// Whether `err` was caused by renaming a file across filesystems.
+fn is_cross_device_error(err: &io::Error) -> bool {
+    // `EXDEV` on unix and `ERROR_NOT_SAME_DEVICE` on windows.
+    let code = if cfg!(windows) { 17 } else { 18 };
+    err.raw_os_error() == Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_replaces_file() {
+        let dir = env_temp_dir("atomic-write");
+        let file = dir.join("marker");
+
+        atomic_write(&file, "first").unwrap();
+        atomic_write(&file, "second").unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+
+        // No temporary files are left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(atomic_write(dir.join("missing").join("file"), "x").is_err());
+        assert!(atomic_write(&dir, "x").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn atomic_write_sets_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env_temp_dir("atomic-write-permissions");
+        let file = dir.join("script.sh");
+
+        atomic_write_with_permissions(&file, "#!/bin/sh\n", Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+
+        // The permissions of an existing file are kept.
+        atomic_write(&file, "#!/bin/sh\nexit 0\n").unwrap();
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn env_temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("embuild-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
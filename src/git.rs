@@ -284,7 +284,12 @@ impl Repository {
             let cores = std::thread::available_parallelism()?;
             let jobs = format!("--jobs={cores}");
 
-            cmd!(GIT, "clone", jobs,"--recursive", @depth, @branch, &url, &self.worktree).run()?;
+            let clone_url = options.mirror_url.as_deref().unwrap_or(url);
+            cmd!(GIT, "clone", jobs,"--recursive", @depth, @branch, clone_url, &self.worktree)
+                .run()?;
+            if clone_url != url {
+                cmd!(GIT, @self.git_args(), "remote", "set-url", "origin", url).run()?;
+            }
 
             if let Some(Ref::Commit(s)) = options.force_ref {
                 cmd!(GIT, @self.git_args(), "checkout", s).run()?;
@@ -441,6 +446,11 @@ pub struct CloneOptions {
     /// Note that this option is ignored when [`force_ref`](Self::force_ref) specifies a
     /// commit.
     pub depth: Option<NonZeroU64>,
+    /// The url to clone from instead of the remote url (ex. a mirror).
+    ///
+    /// The remote of the clone is still set to the remote url, so that an existing
+    /// repository is not cloned again if this url changes.
+    pub mirror_url: Option<String>,
 }
 
 impl CloneOptions {
@@ -493,13 +503,21 @@ impl CloneOptions {
         self.depth = Some(NonZeroU64::new(depth).expect("depth must be greater than zero"));
         self
     }
+
+    /// The url to clone from instead of the remote url (ex. a mirror).
+    ///
+    /// See [`mirror_url`](Self::mirror_url) for more info.
+    pub fn mirror_url(mut self, mirror_url: impl Into<String>) -> Self {
+        self.mirror_url = Some(mirror_url.into());
+        self
+    }
 }
 
 pub mod sdk {
     use std::collections::hash_map::DefaultHasher;
     use std::fs;
     use std::hash::{Hash, Hasher};
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use anyhow::{anyhow, Context, Result};
 
@@ -515,6 +533,20 @@ pub mod sdk {
     }
 
     impl RemoteSdk {
+        /// The directory the repository is cloned into by
+        /// [`open_or_clone`](Self::open_or_clone).
+        pub fn repo_path(&self, install_dir: &Path, managed_repo_dir_base: &str) -> PathBuf {
+            // Only append a hash of the git remote URL to the parent folder name of the
+            // repository if this is not the default remote.
+            let folder_name = if let Some(hash) = self.url_hash() {
+                format!("{managed_repo_dir_base}-{hash}")
+            } else {
+                managed_repo_dir_base.to_owned()
+            };
+
+            install_dir.join(folder_name).join(self.repo_dir())
+        }
+
         /// Clone the repository or open if it exists and matches [`RemoteSdk::git_ref`].
         pub fn open_or_clone(
             &self,
@@ -523,21 +555,14 @@ pub mod sdk {
             default_repo: &str,
             managed_repo_dir_base: &str,
         ) -> Result<git::Repository> {
-            // Only append a hash of the git remote URL to the parent folder name of the
-            // repository if this is not the default remote.
-            let folder_name = if let Some(hash) = self.url_hash() {
-                format!("{managed_repo_dir_base}-{hash}")
-            } else {
-                managed_repo_dir_base.to_owned()
-            };
-            let repos_dir = install_dir.join(folder_name);
+            let repo_path = self.repo_path(install_dir, managed_repo_dir_base);
+            let repos_dir = repo_path.parent().unwrap();
             if !repos_dir.exists() {
-                fs::create_dir(&repos_dir).with_context(|| {
+                fs::create_dir(repos_dir).with_context(|| {
                     anyhow!("could not create folder '{}'", repos_dir.display())
                 })?;
             }
 
-            let repo_path = repos_dir.join(self.repo_dir());
             let mut repository = git::Repository::new(repo_path);
 
             repository.clone_ext(